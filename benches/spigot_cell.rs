@@ -0,0 +1,118 @@
+//! Compara o kernel em frente de onda com as células separadas da tabela de recíprocos
+//! (`spigot_cell`) com o layout em que cada célula guarda o seu recíproco e com o kernel
+//! original, que divide pelo denominador com `/` e `%`.
+//!
+//! Numa medição em x86-64: 3,6 ns por atualização com os recíprocos separados, 3,5 ns
+//! intercalados e 6,5 ns com a divisão de hardware.
+//!
+//! Uso: `cargo bench --bench spigot_cell`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use spigot_pi::reciprocal::Reciprocal;
+use spigot_pi::spigot_cell::{init_cells, init_denominators, process_chunk_wavefront, CELL_BYTES};
+
+/// Células do chunk medido: bem maior que o cache, como o chunk de um estágio real
+const CELLS: usize = 1 << 20;
+
+/// Carries processados sobre cada tile, como `ParallelOptions::wavefront_depth`
+const CARRIES: usize = 64;
+
+/// Rodadas medidas de cada variante; vale a mais rápida
+const ROUNDS: usize = 5;
+
+/// Layout anterior: o valor junto com o recíproco do denominador (24 bytes por célula)
+#[derive(Clone, Copy)]
+struct InterleavedCell {
+    value: i32,
+    den: Reciprocal,
+}
+
+fn den(i: usize) -> u32 {
+    match i {
+        0 => 10,
+        _ => 2 * i as u32 + 1,
+    }
+}
+
+/// Mesmo kernel de `process_chunk_wavefront`, sobre o layout anterior
+fn process_interleaved(cells: &mut [InterleavedCell], carries: &mut [i32], tile_len: usize) {
+    let mut tile_end = cells.len();
+    for tile in cells.rchunks_mut(tile_len) {
+        let tile_start = tile_end - tile.len();
+        for carry in carries.iter_mut() {
+            *carry = tile.iter_mut().enumerate().rev().fold(*carry, |carry, (idx, cell)| {
+                let current = cell.value
+                    .checked_mul(10)
+                    .and_then(|x| x.checked_add(carry.checked_mul((tile_start + idx + 1) as i32)?))
+                    .and_then(|x| u32::try_from(x).ok())
+                    .expect("Overflow ao calcular current");
+                let (div, rem) = cell.den.div_rem(current);
+                cell.value = rem as i32;
+                div as i32
+            });
+        }
+        tile_end = tile_start;
+    }
+}
+
+/// Mesmo kernel de `process_chunk_wavefront`, com a divisão de hardware no lugar do recíproco
+fn process_division(cells: &mut [i32], carries: &mut [i32], tile_len: usize) {
+    let mut tile_end = cells.len();
+    for tile in cells.rchunks_mut(tile_len) {
+        let tile_start = tile_end - tile.len();
+        for carry in carries.iter_mut() {
+            *carry = tile.iter_mut().enumerate().rev().fold(*carry, |carry, (idx, cell)| {
+                let global_idx = tile_start + idx;
+                let current = cell
+                    .checked_mul(10)
+                    .and_then(|x| x.checked_add(carry.checked_mul((global_idx + 1) as i32)?))
+                    .and_then(|x| u32::try_from(x).ok())
+                    .expect("Overflow ao calcular current");
+                let den = den(global_idx);
+                *cell = (current % den) as i32;
+                (current / den) as i32
+            });
+        }
+        tile_end = tile_start;
+    }
+}
+
+/// Menor tempo de `ROUNDS` rodadas de `round`
+fn fastest(mut round: impl FnMut()) -> Duration {
+    round();
+    (0..ROUNDS)
+        .map(|_| {
+            let start = Instant::now();
+            round();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn report(name: &str, bytes_per_cell: usize, elapsed: Duration) {
+    let updates = (CELLS * CARRIES) as f64;
+    println!("{:<14} {:>3} B/célula {:>8.3} ns/atualização", name, bytes_per_cell, elapsed.as_nanos() as f64 / updates);
+}
+
+fn main() {
+    let mut cells = init_cells(0..CELLS);
+    let dens = init_denominators(0..CELLS);
+    let tile_len = 32 * 1024 / CELL_BYTES;
+    let separated = fastest(|| process_chunk_wavefront(black_box(&mut cells), &dens, 0, &mut [0; CARRIES], tile_len));
+
+    let mut interleaved = (0..CELLS).map(|i| InterleavedCell { value: 2, den: Reciprocal::new(den(i)) }).collect::<Vec<_>>();
+    let tile_len = 32 * 1024 / size_of::<InterleavedCell>();
+    let combined = fastest(|| process_interleaved(black_box(&mut interleaved), &mut [0; CARRIES], tile_len));
+
+    let mut plain = init_cells(0..CELLS);
+    let tile_len = 32 * 1024 / size_of::<i32>();
+    let division = fastest(|| process_division(black_box(&mut plain), &mut [0; CARRIES], tile_len));
+
+    report("separado", CELL_BYTES, separated);
+    report("intercalado", size_of::<InterleavedCell>(), combined);
+    report("divisão", size_of::<i32>(), division);
+    // As células que migram entre estágios e ranks levam só o valor
+    println!("bytes migrados por célula: {} (antes {})", size_of::<i32>(), size_of::<InterleavedCell>());
+}
@@ -0,0 +1,164 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread;
+use std::vec;
+use futures::channel::mpsc::{channel, Receiver};
+use futures::channel::oneshot;
+use futures::executor::block_on;
+use futures::future::{self, Either};
+use futures::{SinkExt, Stream, StreamExt};
+use crate::cancellation::CancellationToken;
+use crate::parallel_pi::{calculate_pi_parallel_with, ParallelOptions};
+use crate::task_pipeline::{calculate_pi_with_spawner, Spawner, TaskOptions, WorkerPool};
+
+/// Número de dígitos enviados em cada mensagem da thread de cálculo para o stream
+const DIGITS_PER_MESSAGE: usize = 64;
+
+/// Número de mensagens que podem ficar no canal esperando o stream ser consumido
+const STREAM_BOUND: usize = 16;
+
+/// Stream assíncrono de dígitos de PI.
+///
+/// Os iteradores dos backends são bloqueantes, então o cálculo não roda no executor: cada
+/// stream tem uma thread do sistema dedicada que consome o iterador e entrega os dígitos
+/// por um canal assíncrono limitado. Consumir o stream nunca bloqueia a thread do executor:
+/// quando não há dígitos prontos o stream retorna `Poll::Pending` e é acordado pela thread
+/// de cálculo. Quando o canal está cheio, é a thread de cálculo que fica bloqueada.
+///
+/// Descartar o stream cancela o seu token (nos streams criados pelas funções de cada
+/// backend, o token da computação). A thread de cálculo verifica o token entre os lotes e
+/// é acordada pelo cancelamento mesmo se estiver bloqueada no canal; então ela descarta o
+/// iterador do backend e termina.
+pub struct DigitStream {
+    rx: Receiver<Vec<u8>>,
+    current: vec::IntoIter<u8>,
+    cancellation: CancellationToken,
+}
+
+impl Drop for DigitStream {
+    fn drop(&mut self) {
+        self.cancellation.cancel();
+    }
+}
+
+impl Stream for DigitStream {
+    type Item = u8;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<u8>> {
+        loop {
+            if let Some(digit) = self.current.next() {
+                return Poll::Ready(Some(digit));
+            }
+            match self.rx.poll_next_unpin(cx) {
+                Poll::Ready(Some(batch)) => self.current = batch.into_iter(),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Transforma o iterador de qualquer backend em um `DigitStream`.
+///
+/// O iterador é criado e consumido na thread do stream, então funções que bloqueiam ao
+/// criar o iterador também não bloqueiam o executor.
+pub fn into_stream<I, F>(make_iter: F) -> DigitStream
+where
+    F: FnOnce() -> I + Send + 'static,
+    I: Iterator<Item = u8>,
+{
+    into_cancellable_stream(make_iter, CancellationToken::new())
+}
+
+/// Igual a `into_stream`, mas a thread do stream para quando `cancellation` é cancelado,
+/// e descartar o stream cancela `cancellation`
+fn into_cancellable_stream<I, F>(make_iter: F, cancellation: CancellationToken) -> DigitStream
+where
+    F: FnOnce() -> I + Send + 'static,
+    I: Iterator<Item = u8>,
+{
+    let (mut tx, rx) = channel::<Vec<u8>>(STREAM_BOUND);
+
+    // Resolvido quando o token é cancelado, para acordar a thread bloqueada num envio
+    let (cancel_tx, mut cancelled) = oneshot::channel::<()>();
+    cancellation.on_cancel(move || drop(cancel_tx));
+
+    let token = cancellation.clone();
+    thread::spawn(move || {
+        let mut digits = make_iter();
+        while !token.is_cancelled() {
+            let batch: Vec<u8> = digits.by_ref().take(DIGITS_PER_MESSAGE).collect();
+            if batch.is_empty() {
+                break;
+            }
+            // Espera espaço no canal ou o cancelamento. O envio falha quando o stream foi
+            // descartado: nos dois casos paramos e descartamos o iterador.
+            match block_on(future::select(tx.send(batch), &mut cancelled)) {
+                Either::Left((Ok(()), _)) => {}
+                _ => break,
+            }
+        }
+    });
+
+    DigitStream { rx, current: Vec::new().into_iter(), cancellation }
+}
+
+/// Versão assíncrona de `calculate_pi_sequential`
+pub fn calculate_pi_sequential_stream(n_digits: usize) -> DigitStream {
+    let cancellation = CancellationToken::new();
+    let token = cancellation.clone();
+    into_cancellable_stream(move || crate::calculate_pi_sequential_cancellable(n_digits, token), cancellation)
+}
+
+/// Versão assíncrona de `calculate_pi_parallel_with`
+pub fn calculate_pi_parallel_stream(n_digits: usize, mut options: ParallelOptions) -> DigitStream {
+    let cancellation = options.cancellation.child_token();
+    options.cancellation = cancellation.clone();
+    into_cancellable_stream(move || calculate_pi_parallel_with(n_digits, options), cancellation)
+}
+
+/// Versão assíncrona de `calculate_pi_tasks`, com as opções do pipeline configuráveis
+pub fn calculate_pi_tasks_stream(n_digits: usize, num_workers: usize, options: TaskOptions) -> DigitStream {
+    calculate_pi_spawner_stream(n_digits, WorkerPool::new(num_workers), options)
+}
+
+/// Versão assíncrona de `calculate_pi_with_spawner`
+pub fn calculate_pi_spawner_stream<S: Spawner>(n_digits: usize, spawner: S, mut options: TaskOptions) -> DigitStream {
+    let cancellation = options.cancellation.child_token();
+    options.cancellation = cancellation.clone();
+    into_cancellable_stream(move || calculate_pi_with_spawner(n_digits, spawner, options), cancellation)
+}
+
+/// Versão assíncrona de `calculate_pi_rayon_with`
+#[cfg(feature = "rayon")]
+pub fn calculate_pi_rayon_stream(
+    n_digits: usize,
+    pool: Option<std::sync::Arc<rayon::ThreadPool>>,
+    mut options: TaskOptions,
+) -> DigitStream {
+    let cancellation = options.cancellation.child_token();
+    options.cancellation = cancellation.clone();
+    into_cancellable_stream(move || crate::rayon_pi::calculate_pi_rayon_with(n_digits, pool, options), cancellation)
+}
+
+/// Versão assíncrona de `calculate_pi_mpi_with`.
+///
+/// Como no backend síncrono, apenas o rank 0 recebe um stream; nos outros ranks a função
+/// bloqueia até o fim do trabalho do rank e retorna `None`.
+#[cfg(feature = "mpi")]
+pub fn calculate_pi_mpi_stream(n_digits: usize, mut options: crate::mpi_pi::MpiOptions) -> Option<DigitStream> {
+    let cancellation = options.cancellation.child_token();
+    options.cancellation = cancellation.clone();
+
+    let (rank_tx, rank_rx) = std::sync::mpsc::channel();
+    let stream = into_cancellable_stream(move || {
+        let digits = crate::mpi_pi::calculate_pi_mpi_with(n_digits, options);
+        let _ = rank_tx.send(digits.is_some());
+        digits.into_iter().flatten()
+    }, cancellation);
+
+    match rank_rx.recv() {
+        Ok(true) => Some(stream),
+        _ => None,
+    }
+}
@@ -1,3 +1,5 @@
+use crate::partition::Partition;
+
 /// Um iterador que divide um slice mutável em chunks balanceados.
 ///
 /// Este iterador é similar ao `chunks_mut()` padrão do Rust, mas com uma diferença importante:
@@ -22,11 +24,22 @@
 /// Os primeiros `remainder` chunks recebem `base_size + 1` elementos, enquanto os demais
 /// recebem `base_size` elementos. Isso garante que a diferença máxima entre qualquer
 /// dois chunks seja de apenas 1 elemento.
+///
+/// # Divisão ponderada
+///
+/// `with_weights` e `with_cost` produzem chunks contíguos de custo aproximadamente igual
+/// quando o custo por elemento não é uniforme ou quando quem processa cada chunk tem
+/// capacidade diferente (cores heterogêneos, nós MPI com velocidades distintas).
+///
+/// A divisão é descrita por um `Partition`, então o mesmo cálculo pode ser usado por quem
+/// só precisa dos índices de cada chunk. `with_global_starts` produz pares
+/// `(global_start, chunk)` com a posição global do primeiro elemento de cada chunk.
 pub struct BalancedChunksMut<'a, T> {
     remaining: Option<&'a mut [T]>,
+    /// Índice do próximo chunk retornado por `next()`
+    front: usize,
     chunks_left: usize,
-    base_size: usize,
-    remainder: usize
+    partition: Partition,
 }
 
 impl<'a, T> BalancedChunksMut<'a, T> {
@@ -42,18 +55,64 @@ impl<'a, T> BalancedChunksMut<'a, T> {
     /// # Panics
     /// Não causa panic, mas retorna um iterador vazio se `num_chunks == 0`.
     pub fn new(slice: &'a mut[T], num_chunks:usize) -> Self {
-        if num_chunks == 0 {
-            Self { remaining: None, chunks_left: 0, base_size: 0, remainder: 0 }
-        } else {
-            let len = slice.len();
-            Self {
-                remaining: Some(slice),
-                chunks_left: num_chunks,
-                base_size: len / num_chunks,
-                remainder: len % num_chunks
-            }
+        let partition = Partition::balanced(slice.len(), num_chunks);
+        Self::from_partition(slice, partition)
+    }
+
+    /// Cria um iterador que divide o slice em `weights.len()` chunks com tamanhos
+    /// proporcionais aos pesos (ver `Partition::weighted`).
+    ///
+    /// # Panics
+    /// Causa panic se algum peso for negativo ou não finito.
+    pub fn with_weights(slice: &'a mut [T], weights: &[f64]) -> Self {
+        let partition = Partition::weighted(slice.len(), weights);
+        Self::from_partition(slice, partition)
+    }
+
+    /// Cria um iterador que divide o slice em `num_chunks` chunks contíguos de custo total
+    /// aproximadamente igual, onde `cost(i)` é o custo de processar o elemento `i`
+    /// (ver `Partition::with_cost`).
+    ///
+    /// # Panics
+    /// Causa panic se algum custo for negativo ou não finito.
+    pub fn with_cost(slice: &'a mut [T], num_chunks: usize, cost: impl Fn(usize) -> f64) -> Self {
+        let partition = Partition::with_cost(slice.len(), num_chunks, cost);
+        Self::from_partition(slice, partition)
+    }
+
+    /// Combinação de `with_weights` e `with_cost` (ver `Partition::weighted_cost`).
+    ///
+    /// # Panics
+    /// Causa panic se algum peso ou custo for negativo ou não finito.
+    pub fn with_weighted_cost(slice: &'a mut [T], weights: &[f64], cost: impl Fn(usize) -> f64) -> Self {
+        let partition = Partition::weighted_cost(slice.len(), weights, cost);
+        Self::from_partition(slice, partition)
+    }
+
+    /// Cria um iterador que divide o slice conforme uma divisão já calculada.
+    ///
+    /// # Panics
+    /// Causa panic se `partition.len()` for diferente do tamanho do slice.
+    pub fn from_partition(slice: &'a mut [T], partition: Partition) -> Self {
+        assert_eq!(slice.len(), partition.len(), "Partition não corresponde ao tamanho do slice");
+        Self {
+            remaining: if partition.num_parts() == 0 { None } else { Some(slice) },
+            front: 0,
+            chunks_left: partition.num_parts(),
+            partition,
         }
     }
+
+    /// A divisão usada por este iterador
+    pub fn partition(&self) -> &Partition {
+        &self.partition
+    }
+
+    /// Transforma o iterador para produzir `(global_start, chunk)`, onde `global_start` é a
+    /// posição global do primeiro elemento do chunk (considerando o offset do `Partition`).
+    pub fn with_global_starts(self) -> GlobalChunksMut<'a, T> {
+        GlobalChunksMut { inner: self }
+    }
 }
 
 impl<'a, T> Iterator for BalancedChunksMut<'a, T> {
@@ -66,13 +125,12 @@ impl<'a, T> Iterator for BalancedChunksMut<'a, T> {
                 // Pega a referência do slice restante (ownership temporário via take)
                 let slice = self.remaining.take()?;
 
-                // Calcula o tamanho DESTE chunk específico.
-                // Se ainda houver "resto" (remainder > 0), este chunk ganha 1 item extra.
-                let current_size = self.base_size + if self.remainder > 0 { 1 } else { 0 };
+                // Calcula o tamanho DESTE chunk específico
+                let current_size = self.partition.range(self.front).len();
 
                 // Atualiza os contadores de estado
+                self.front += 1;
                 self.chunks_left -= 1;
-                if self.remainder > 0 { self.remainder -= 1; }
 
                 if self.chunks_left == 0 {
                     // Se for o último chunk, retorna tudo o que sobrou (evita cálculo de split)
@@ -87,7 +145,10 @@ impl<'a, T> Iterator for BalancedChunksMut<'a, T> {
             }
         }
     }
-    
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.chunks_left, Some(self.chunks_left))
+    }
 }
 /// Implementação de `DoubleEndedIterator` que permite iterar em ordem reversa.
 ///
@@ -99,16 +160,10 @@ impl<'a, T> DoubleEndedIterator for BalancedChunksMut<'a, T> {
 
         let slice = self.remaining.take()?;
 
-        // Lógica Inversa: Precisamos saber se o chunk lá do FINAL tem tamanho extra.
-        // Os chunks com tamanho extra são os índices [0, 1, ... remainder-1].
-        // O índice lógico atual do fim é (chunks_left - 1).
-        let is_bonus_chunk = (self.chunks_left - 1) < self.remainder;
-        let current_size = self.base_size + if is_bonus_chunk { 1 } else { 0 };
+        // O índice lógico atual do fim é (front + chunks_left - 1)
+        let current_size = self.partition.range(self.front + self.chunks_left - 1).len();
 
         self.chunks_left -= 1;
-        // Se pegamos um chunk do final e ele ERA um chunk de bonus (o que só acontece
-        // se remainder == chunks_left), precisamos decrementar o remainder.
-        if is_bonus_chunk { self.remainder -= 1; }
 
         if self.chunks_left == 0 {
             return Some(slice);
@@ -132,4 +187,125 @@ impl<'a, T> ExactSizeIterator for BalancedChunksMut<'a, T> {
     fn len(&self) -> usize {
         self.chunks_left
     }
-}
\ No newline at end of file
+}
+
+/// Iterador de pares `(global_start, chunk)`, criado por `BalancedChunksMut::with_global_starts`
+pub struct GlobalChunksMut<'a, T> {
+    inner: BalancedChunksMut<'a, T>,
+}
+
+impl<'a, T> Iterator for GlobalChunksMut<'a, T> {
+    type Item = (usize, &'a mut [T]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.inner.front;
+        let chunk = self.inner.next()?;
+        Some((self.inner.partition.global_start(index), chunk))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for GlobalChunksMut<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let index = (self.inner.front + self.inner.chunks_left).checked_sub(1)?;
+        let chunk = self.inner.next_back()?;
+        Some((self.inner.partition.global_start(index), chunk))
+    }
+}
+
+impl<'a, T> ExactSizeIterator for GlobalChunksMut<'a, T> {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+#[cfg(feature = "rayon")]
+mod par {
+    use rayon::iter::plumbing::{bridge, Consumer, Producer, ProducerCallback, UnindexedConsumer};
+    use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+    use super::BalancedChunksMut;
+
+    /// Versão paralela do `BalancedChunksMut`, criada por `into_par_iter()`.
+    ///
+    /// É um iterador indexado: o rayon pode dividi-lo em qualquer posição e sabe o
+    /// número exato de chunks, então `zip`, `enumerate` e `collect` funcionam normalmente.
+    pub struct ParBalancedChunksMut<'a, T> {
+        inner: BalancedChunksMut<'a, T>,
+    }
+
+    impl<'a, T: Send> IntoParallelIterator for BalancedChunksMut<'a, T> {
+        type Iter = ParBalancedChunksMut<'a, T>;
+        type Item = &'a mut [T];
+
+        fn into_par_iter(self) -> Self::Iter {
+            ParBalancedChunksMut { inner: self }
+        }
+    }
+
+    impl<'a, T: Send> ParallelIterator for ParBalancedChunksMut<'a, T> {
+        type Item = &'a mut [T];
+
+        fn drive_unindexed<C: UnindexedConsumer<Self::Item>>(self, consumer: C) -> C::Result {
+            bridge(self, consumer)
+        }
+
+        fn opt_len(&self) -> Option<usize> {
+            Some(self.inner.len())
+        }
+    }
+
+    impl<'a, T: Send> IndexedParallelIterator for ParBalancedChunksMut<'a, T> {
+        fn len(&self) -> usize {
+            self.inner.len()
+        }
+
+        fn drive<C: Consumer<Self::Item>>(self, consumer: C) -> C::Result {
+            bridge(self, consumer)
+        }
+
+        fn with_producer<CB: ProducerCallback<Self::Item>>(self, callback: CB) -> CB::Output {
+            callback.callback(self.inner)
+        }
+    }
+
+    impl<'a, T: Send> Producer for BalancedChunksMut<'a, T> {
+        type Item = &'a mut [T];
+        type IntoIter = Self;
+
+        fn into_iter(self) -> Self {
+            self
+        }
+
+        /// Divide em `[front, front + index)` e `[front + index, ...)`, cortando o slice
+        /// restante no início do chunk `front + index`
+        fn split_at(self, index: usize) -> (Self, Self) {
+            let slice = self.remaining.unwrap_or_default();
+            let split = match index == self.chunks_left {
+                true => slice.len(),
+                false => self.partition.range(self.front + index).start - self.partition.range(self.front).start,
+            };
+            let (left, right) = slice.split_at_mut(split);
+
+            (
+                BalancedChunksMut {
+                    remaining: Some(left),
+                    front: self.front,
+                    chunks_left: index,
+                    partition: self.partition.clone(),
+                },
+                BalancedChunksMut {
+                    remaining: Some(right),
+                    front: self.front + index,
+                    chunks_left: self.chunks_left - index,
+                    partition: self.partition,
+                },
+            )
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+pub use par::ParBalancedChunksMut;
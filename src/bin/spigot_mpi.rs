@@ -3,21 +3,43 @@
 // Versão: mpi 0.8.1
 // AI_GENERATED_CODE_START
 #[cfg(feature = "mpi")]
-use spigot_pi::calculate_pi_mpi;
+use spigot_pi::{calculate_pi_mpi, calculate_pi_mpi_to_file, MpiOptions};
 
 #[cfg(feature = "mpi")]
 fn main() {
     let n_digits = 100;
 
+    // Com um caminho como argumento, os dígitos vão para um arquivo gravado com MPI-IO
+    // (mais um manifest) em vez do stdout
+    // Exemplo: mpirun -np 4 target/release/spigot_mpi /dados/pi.txt
+    if let Some(path) = std::env::args().nth(1) {
+        match calculate_pi_mpi_to_file(n_digits, &path, MpiOptions::default()) {
+            Ok(Some(manifest)) => eprint!("{}", manifest),
+            Ok(None) => {}
+            Err(error) => {
+                eprintln!("Erro ao gravar {}: {}", path, error);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     // Calcula os dígitos de PI usando a implementação MPI distribuída
     // Nota: Esta função deve ser executada com mpirun/mpiexec
     // Exemplo: mpirun -np 4 target/release/spigot_pi --bin spigot_mpi
-    if let Some(digits) = calculate_pi_mpi(n_digits) {
+    if let Some(mut digits) = calculate_pi_mpi(n_digits) {
         println!("Calculando PI com MPI:");
-        for d in digits {
+        for d in digits.by_ref() {
             print!("{}", d);
         }
         println!();
+
+        // Estatísticas por rank no stderr, para identificar o gargalo
+        eprint!("{}", digits.stats().report());
+        if let Some(failure) = digits.failure() {
+            eprintln!("Erro: {}", failure);
+            std::process::exit(1);
+        }
     }
 }
 
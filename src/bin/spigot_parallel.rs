@@ -1,17 +1,31 @@
-use spigot_pi::calculate_pi_parallel;
+use std::time::Duration;
+use spigot_pi::{calculate_pi_parallel_with, ParallelOptions, ProgressHook};
 
 fn main() {
     let n_digits = 100000;
     let num_threads = 12;
     let channel_bound = 12;
 
+    // Mostra o andamento no stderr, para não misturar com os dígitos no stdout
+    let progress = ProgressHook::new(Duration::from_secs(1), |p| {
+        let eta = p.eta.map(|eta| format!("{:.0}s", eta.as_secs_f64())).unwrap_or_else(|| "?".into());
+        eprint!("\r{:5.1}% ({}/{} dígitos, ETA {})", p.fraction() * 100.0, p.digits_emitted, p.total_digits, eta);
+    });
+
     // Calcula os dígitos de PI usando a implementação paralela
-    let pi_iterator = calculate_pi_parallel(n_digits, num_threads, channel_bound);
+    let mut pi_iterator = calculate_pi_parallel_with(n_digits, ParallelOptions {
+        num_threads,
+        channel_bound,
+        progress: Some(progress),
+        ..ParallelOptions::default()
+    });
 
     print!("PI: 3.");
     // Pula o primeiro dígito (que é o 3) e imprime os demais
-    for digit in pi_iterator.skip(1) {
+    for digit in pi_iterator.by_ref().skip(1) {
         print!("{}", digit);
     }
     println!();
-}
\ No newline at end of file
+    eprintln!();
+    eprint!("{}", pi_iterator.stats().report());
+}
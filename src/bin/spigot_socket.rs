@@ -0,0 +1,65 @@
+use std::process;
+use spigot_pi::{run_socket_worker, DistributedOptions, SocketAddress, SocketCoordinator};
+
+const USAGE: &str = "Uso:
+  spigot_socket coordinator <endereço> <workers> [dígitos]
+  spigot_socket worker <endereço do coordinator> [endereço local]
+
+Endereços: host:porta (TCP) ou unix:<caminho>. O endereço local padrão é 0.0.0.0:0.";
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+
+    // Exemplo em uma máquina, com 3 processos:
+    //   spigot_socket coordinator 127.0.0.1:7000 2 1000
+    //   spigot_socket worker 127.0.0.1:7000    (em dois outros terminais)
+    match args[..] {
+        ["coordinator", address, workers, ref rest @ ..] if rest.len() <= 1 => {
+            let workers = workers.parse().unwrap_or_else(|_| usage());
+            let n_digits = rest.first().map_or(Some(100), |n| n.parse().ok()).unwrap_or_else(|| usage());
+            coordinator(parse_address(address), workers, n_digits);
+        }
+        ["worker", coordinator, ref rest @ ..] if rest.len() <= 1 => {
+            let listen = parse_address(rest.first().copied().unwrap_or("0.0.0.0:0"));
+            match run_socket_worker(&parse_address(coordinator), &listen, DistributedOptions::default()) {
+                Ok(None) => {}
+                Ok(Some(failure)) => exit_with(&failure),
+                Err(error) => exit_with(&error),
+            }
+        }
+        _ => usage(),
+    }
+}
+
+fn coordinator(address: SocketAddress, workers: usize, n_digits: usize) {
+    let coordinator = SocketCoordinator::bind(&address).unwrap_or_else(|error| exit_with(&error));
+    eprintln!("Esperando {} workers em {}", workers, coordinator.local_address().unwrap_or(address));
+    let mut digits = coordinator.run(n_digits, workers, DistributedOptions::default()).unwrap_or_else(|error| exit_with(&error));
+
+    println!("Calculando PI com sockets:");
+    for d in digits.by_ref() {
+        print!("{}", d);
+    }
+    println!();
+
+    // Estatísticas por rank no stderr, para identificar o gargalo
+    eprint!("{}", digits.stats().report());
+    if let Some(failure) = digits.failure() {
+        exit_with(&failure);
+    }
+}
+
+fn parse_address(address: &str) -> SocketAddress {
+    address.parse().unwrap_or_else(|error| exit_with(&error))
+}
+
+fn exit_with(error: &dyn std::fmt::Display) -> ! {
+    eprintln!("Erro: {}", error);
+    process::exit(1);
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
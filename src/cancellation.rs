@@ -0,0 +1,114 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+type Callback = Box<dyn FnOnce() + Send + 'static>;
+
+/// Token de cancelamento cooperativo de uma computação em andamento.
+///
+/// Todos os backends aceitam um token nas suas opções. Ao chamar `cancel()`, os estágios
+/// (ou ranks) param no próximo ponto de verificação, os canais entre eles são fechados para
+/// acordar quem estiver bloqueado, a memória dos chunks é liberada e o iterador de dígitos
+/// termina.
+///
+/// Clones compartilham o mesmo estado: cancelar qualquer um cancela todos.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    /// Ações executadas uma única vez, no cancelamento
+    callbacks: Mutex<Vec<Callback>>,
+    /// Tokens filhos, cancelados junto com este. `Weak` para não manter vivos os filhos já
+    /// descartados; os que morreram são removidos a cada novo filho.
+    children: Mutex<Vec<Weak<Inner>>>,
+}
+
+impl CancellationToken {
+    /// Cria um token ainda não cancelado
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancela a computação. Chamadas repetidas não têm efeito.
+    pub fn cancel(&self) {
+        if self.inner.cancelled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let callbacks = std::mem::take(&mut *self.inner.callbacks.lock().unwrap());
+        for callback in callbacks {
+            callback();
+        }
+        let children = std::mem::take(&mut *self.inner.children.lock().unwrap());
+        for inner in children.iter().filter_map(Weak::upgrade) {
+            CancellationToken { inner }.cancel();
+        }
+    }
+
+    /// Retorna `true` se o token foi cancelado
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Cria um token que é cancelado junto com este, mas que pode ser cancelado sozinho
+    /// sem afetar este (usado para encerrar uma única computação quando o seu iterador é
+    /// descartado).
+    ///
+    /// Um token de longa duração pode criar filhos sem limite: só os filhos ainda vivos
+    /// ficam registrados nele.
+    pub fn child_token(&self) -> CancellationToken {
+        let child = CancellationToken::new();
+        let mut children = self.inner.children.lock().unwrap();
+        // Verificado com o lock para não perder um cancel() concorrente
+        if self.is_cancelled() {
+            drop(children);
+            child.cancel();
+        } else {
+            children.retain(|weak| weak.strong_count() > 0);
+            children.push(Arc::downgrade(&child.inner));
+        }
+        child
+    }
+
+    /// Registra uma ação a ser executada no cancelamento. Se o token já foi cancelado, a
+    /// ação é executada imediatamente.
+    pub(crate) fn on_cancel(&self, callback: impl FnOnce() + Send + 'static) {
+        let mut callbacks = self.inner.callbacks.lock().unwrap();
+        // Verificado com o lock para não perder um cancel() concorrente
+        if self.is_cancelled() {
+            drop(callbacks);
+            callback();
+        } else {
+            callbacks.push(Box::new(callback));
+        }
+    }
+}
+
+impl fmt::Debug for CancellationToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancellationToken").field("cancelled", &self.is_cancelled()).finish()
+    }
+}
+
+/// Iterador que cancela o token quando é descartado, encerrando a computação que o alimenta
+pub(crate) struct CancelOnDrop<I> {
+    pub(crate) inner: I,
+    pub(crate) token: CancellationToken,
+}
+
+impl<I: Iterator> Iterator for CancelOnDrop<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        self.inner.next()
+    }
+}
+
+impl<I> Drop for CancelOnDrop<I> {
+    fn drop(&mut self) {
+        self.token.cancel();
+    }
+}
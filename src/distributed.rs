@@ -0,0 +1,850 @@
+use std::fmt;
+#[cfg(any(feature = "mpi", test))]
+use std::io;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{channel, IntoIter, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use crate::balanced_chunks_mut::BalancedChunksMut;
+use crate::cancellation::CancellationToken;
+#[cfg(any(feature = "mpi", test))]
+use crate::output::{BlockOutput, OutputManifest};
+use crate::parallel_pi::{calculate_pi_parallel_with, ParallelOptions};
+use crate::partition::Partition;
+use crate::pi_digits_iter::PiDigitsIter;
+use crate::progress::{ProgressHook, ProgressIter};
+use crate::reciprocal::Reciprocal;
+use crate::spigot_cell::{init_cells, init_denominators, process_chunk_wavefront, CELL_BYTES};
+use crate::stats::{PipelineStats, StageCounters, StageStats};
+use crate::transport::{decode_u64s, encode_u64s, InMemoryTransport, Message, Tag, Transport};
+
+/// Número de carries entre dois `Checkpoint` enviados pelo rank 0
+const CHECKPOINT_INTERVAL: usize = 16 * 1024;
+
+/// Valor padrão de `DistributedOptions::max_in_flight`
+pub(crate) const DEFAULT_MAX_IN_FLIGHT: usize = 16 * 1024;
+
+/// Espera máxima de um rank pela saída do pipeline de threads antes de verificar de novo
+/// as mensagens recebidas e o cancelamento
+pub(crate) const CANCELLATION_POLL_INTERVAL: Duration = Duration::from_micros(100);
+
+/// Intervalo entre os relatórios de tempo ocioso enviados por cada worker ao rank 0
+const PROGRESS_REPORT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Motivo pelo qual uma computação distribuída foi abortada
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RankFailure {
+    /// O rank falhou ao processar um lote (por exemplo, overflow de uma célula) ou recebeu
+    /// mensagens fora do protocolo
+    Rank(i32),
+    /// O rank não respondeu dentro de `DistributedOptions::receive_timeout`: provavelmente
+    /// o processo morreu (por exemplo, um pod morto por falta de memória)
+    Timeout { rank: i32, timeout: Duration },
+}
+
+impl RankFailure {
+    /// Rank responsável pela falha
+    pub fn rank(&self) -> i32 {
+        match *self {
+            RankFailure::Rank(rank) | RankFailure::Timeout { rank, .. } => rank,
+        }
+    }
+
+    /// Conteúdo de uma mensagem `Abort`: `[0, 0]` no cancelamento, `[1, rank]` numa falha
+    /// e `[2, rank]` num timeout
+    fn encode(failure: Option<RankFailure>) -> Vec<i32> {
+        match failure {
+            None => vec![0, 0],
+            Some(RankFailure::Rank(rank)) => vec![1, rank],
+            Some(RankFailure::Timeout { rank, .. }) => vec![2, rank],
+        }
+    }
+
+    /// Inverso de `encode`. O timeout não trafega: todos os ranks usam as mesmas opções.
+    fn decode(payload: &[i32], timeout: Duration) -> Option<RankFailure> {
+        match *payload {
+            [1, rank] => Some(RankFailure::Rank(rank)),
+            [2, rank] => Some(RankFailure::Timeout { rank, timeout }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RankFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RankFailure::Rank(rank) => write!(f, "o rank {} falhou e abortou a computação", rank),
+            RankFailure::Timeout { rank, timeout } => write!(f, "o rank {} não respondeu em {:?}", rank, timeout),
+        }
+    }
+}
+
+/// Opções de execução do pipeline distribuído, iguais em todos os ranks
+#[derive(Debug, Clone)]
+pub struct DistributedOptions {
+    /// Número de threads (estágios) do pipeline interno de cada rank, que divide o chunk
+    /// local. O padrão usa todos os cores disponíveis para o processo, respeitando o
+    /// limite de CPU do container.
+    pub num_threads: usize,
+    /// Número de carries enviados em cada mensagem entre ranks
+    pub carry_batch: usize,
+    /// Número de células de cada tile ao processar um lote sobre o chunk local
+    pub tile_len: usize,
+    /// Número máximo de dígitos em trânsito no pipeline. O rank 0 só envia novos triggers
+    /// à medida que os resultados voltam, o que limita os buffers de cada rank. Nunca
+    /// fica abaixo de `carry_batch`.
+    pub max_in_flight: usize,
+    /// Token para interromper a computação, observado pelo rank 0. Descartar o iterador
+    /// também interrompe. No cancelamento o rank 0 envia `Abort` a todos os workers.
+    pub cancellation: CancellationToken,
+    /// Callback de progresso, chamado no rank 0. O tempo ocioso de cada rank é o tempo
+    /// bloqueado esperando lotes, reportado periodicamente ao rank 0 pelos demais ranks.
+    pub progress: Option<ProgressHook>,
+    /// Tempo máximo que um rank espera, sem nenhuma atividade, por uma mensagem ou pela
+    /// conclusão de um envio a um vizinho. Ao estourar, o rank aborta o job com
+    /// `RankFailure::Timeout` indicando o vizinho que não respondeu. Deve ser bem maior
+    /// que o tempo de processar um lote sobre o chunk de um rank.
+    pub receive_timeout: Duration,
+}
+
+impl Default for DistributedOptions {
+    fn default() -> Self {
+        Self {
+            num_threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            carry_batch: 64,
+            tile_len: 32 * 1024 / CELL_BYTES,
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            cancellation: CancellationToken::new(),
+            progress: None,
+            receive_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Iterador sobre os dígitos de uma computação distribuída, no rank 0. Os ranks rodam em
+/// uma thread de fundo, que é esperada quando o iterador termina ou é descartado.
+pub struct DistributedDigits {
+    inner: ProgressIter<PiDigitsIter<IntoIter<i32>>>,
+    thread_handle: Option<JoinHandle<()>>,
+    cancellation: CancellationToken,
+    /// Estatísticas de cada rank, preenchidas pelo rank 0 quando os workers terminam
+    stats: Arc<Mutex<Vec<StageStats>>>,
+    /// Motivo pelo qual a computação foi abortada
+    failure: Arc<Mutex<Option<RankFailure>>>,
+}
+
+impl DistributedDigits {
+    /// Iterador sobre os dígitos recebidos em `digits`, produzidos por `rank0_coordinator`
+    /// com `coordinator` na thread `thread_handle`
+    pub(crate) fn new(
+        n_digits: usize,
+        progress: Option<ProgressHook>,
+        digits: Receiver<i32>,
+        thread_handle: JoinHandle<()>,
+        cancellation: CancellationToken,
+        coordinator: &Coordinator,
+    ) -> Self {
+        let rank_idle = coordinator.rank_idle.clone();
+        Self {
+            inner: ProgressIter::new(
+                PiDigitsIter::new(digits.into_iter()),
+                n_digits,
+                progress,
+                move || rank_idle.lock().unwrap().clone(),
+            ),
+            thread_handle: Some(thread_handle),
+            cancellation,
+            stats: coordinator.stats.clone(),
+            failure: coordinator.failure.clone(),
+        }
+    }
+
+    /// Estatísticas de cada rank (o índice é o rank), somando os estágios do pipeline de
+    /// threads de cada um. O rank 0 processa o chunk mais à esquerda. Os valores chegam
+    /// quando os workers terminam, portanto só estão completos depois que o iterador termina.
+    pub fn stats(&self) -> PipelineStats {
+        PipelineStats { stages: self.stats.lock().unwrap().clone() }
+    }
+
+    /// Rank que falhou e abortou a computação, se houver. Nesse caso o iterador termina
+    /// antes de emitir todos os dígitos. Um cancelamento não conta como falha.
+    pub fn failed_rank(&self) -> Option<i32> {
+        self.failure().map(|failure| failure.rank())
+    }
+
+    /// Motivo pelo qual a computação foi abortada, se um rank falhou ou não respondeu
+    pub fn failure(&self) -> Option<RankFailure> {
+        *self.failure.lock().unwrap()
+    }
+
+    /// Espera a thread de fundo terminar, exceto quando um rank deixou de responder: com
+    /// um processo morto a finalização (no MPI, o `MPI_Finalize`) pode nunca retornar, e a
+    /// thread é abandonada
+    fn join(&mut self) {
+        if matches!(self.failure(), Some(RankFailure::Timeout { .. })) {
+            return;
+        }
+        if let Some(handle) = self.thread_handle.take() {
+            // Ignoramos erros de panic na thread filha para não sujar a saída
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Iterator for DistributedDigits {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let digit = self.inner.next();
+        if digit.is_none() {
+            // Espera o rank 0 receber as estatísticas finais dos workers
+            self.join();
+        }
+        digit
+    }
+}
+
+impl Drop for DistributedDigits {
+    fn drop(&mut self) {
+        // Se o iterador for descartado antes do fim, o rank 0 encerra os workers
+        self.cancellation.cancel();
+
+        // Esperamos a thread de fundo terminar o cleanup (no MPI, o MPI_Finalize)
+        self.join();
+    }
+}
+
+/// Estado compartilhado entre o rank 0 e o iterador. Os vetores são dimensionados por
+/// `rank0_coordinator`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Coordinator {
+    /// Tempo ocioso de cada rank, atualizado a cada relatório recebido
+    pub(crate) rank_idle: Arc<Mutex<Vec<Duration>>>,
+    pub(crate) stats: Arc<Mutex<Vec<StageStats>>>,
+    pub(crate) failure: Arc<Mutex<Option<RankFailure>>>,
+}
+
+/// Calcula os dígitos de PI com o pipeline distribuído, simulando `ranks` ranks como
+/// threads deste processo ligadas por um `InMemoryTransport`. Cada rank ainda divide o
+/// seu chunk entre `num_threads` estágios.
+///
+/// Executa exatamente a lógica de `calculate_pi_mpi`, sem precisar de MPI, o que serve
+/// para testes e para comparar o custo da comunicação entre ranks.
+pub fn calculate_pi_in_memory(n_digits: usize, ranks: usize, mut options: DistributedOptions) -> DistributedDigits {
+    // Token desta computação: cancelado pelo token do usuário ou quando o iterador é descartado
+    let cancellation = options.cancellation.child_token();
+    options.cancellation = cancellation.clone();
+
+    let (tx, rx) = channel::<i32>();
+    let coordinator = Coordinator::default();
+    let shared = coordinator.clone();
+    let progress = options.progress.clone();
+
+    let handle = thread::spawn(move || {
+        let mut transports = InMemoryTransport::create(ranks.max(1));
+        let rank0 = transports.remove(0);
+        let options = &options;
+        thread::scope(|scope| {
+            for transport in transports {
+                scope.spawn(move || rank_worker(&transport, n_digits, options));
+            }
+            rank0_coordinator(&rank0, n_digits, options, tx, &shared);
+        });
+    });
+
+    DistributedDigits::new(n_digits, progress, rx, handle, cancellation, &coordinator)
+}
+
+/// Destino dos dígitos que saem do rank 0, ainda sem correção
+pub(crate) trait DigitSink {
+    /// Recebe os próximos dígitos. Retorna `false` se o destino não aceita mais dígitos,
+    /// o que cancela a computação.
+    fn accept(&mut self, digits: &[i32]) -> bool;
+}
+
+impl DigitSink for Sender<i32> {
+    fn accept(&mut self, digits: &[i32]) -> bool {
+        digits.iter().all(|&digit| self.send(digit).is_ok())
+    }
+}
+
+/// Parte do rank 0: coordena o processamento e entrega os dígitos (ainda sem correção) a
+/// `tx`. Com um único rank, calcula tudo localmente com o pipeline de threads.
+pub(crate) fn rank0_coordinator<T: Transport>(
+    transport: &T,
+    n_digits: usize,
+    options: &DistributedOptions,
+    mut tx: impl DigitSink,
+    coordinator: &Coordinator,
+) {
+    let size = transport.size();
+    *coordinator.rank_idle.lock().unwrap() = vec![Duration::ZERO; size as usize];
+    *coordinator.stats.lock().unwrap() = vec![StageStats::default(); size as usize];
+
+    if size > 1 {
+        let stats = pipeline(transport, n_digits, options, &mut tx, coordinator);
+        coordinator.stats.lock().unwrap()[0] = stats;
+        // Um rank que não respondeu também não enviaria as estatísticas
+        let dead_rank = match *coordinator.failure.lock().unwrap() {
+            Some(RankFailure::Timeout { rank, .. }) => Some(rank),
+            _ => None,
+        };
+        receive_stats(transport, options.receive_timeout, &coordinator.stats, dead_rank);
+        if !matches!(*coordinator.failure.lock().unwrap(), Some(RankFailure::Timeout { .. })) {
+            transport.flush(options.receive_timeout);
+        }
+    } else {
+        // Modo single-process: sem workers, o rank 0 calcula tudo localmente com o pipeline
+        // de threads. Os dígitos já saem corrigidos, e passar dígitos menores que 10 pelo
+        // PiDigitsIter da main thread não os altera.
+        let local_options = ParallelOptions {
+            num_threads: options.num_threads,
+            carry_batch: options.carry_batch,
+            tile_len: options.tile_len,
+            cancellation: options.cancellation.clone(),
+            ..ParallelOptions::default()
+        };
+        let mut digits = calculate_pi_parallel_with(n_digits, local_options);
+        if digits.by_ref().any(|digit| !tx.accept(&[digit as i32])) {
+            options.cancellation.cancel();
+        }
+        coordinator.stats.lock().unwrap()[0] = digits.stats().total();
+    }
+    // tx é dropado aqui, o que fecha o channel e sinaliza o fim para o iterador na main
+}
+
+/// Parte do rank 0 quando os dígitos vão direto para um arquivo.
+///
+/// Roda `rank0_coordinator` nesta thread e corrige os dígitos em outra, que os devolve em
+/// blocos de `block_len`. Entre os lotes do pipeline esta thread grava os blocos já
+/// prontos com `write_at(offset, bloco)`, então o rank 0 não guarda os dígitos até o fim
+/// e todas as chamadas de `write_at` vêm da thread que usa o transporte.
+///
+/// # Retorna
+/// O manifest dos dígitos gravados, que cobre menos de `n_digits` dígitos se a computação
+/// foi interrompida, ou o primeiro erro de `write_at`, que cancela a computação.
+#[cfg(any(feature = "mpi", test))]
+pub(crate) fn rank0_to_file<T: Transport>(
+    transport: &T,
+    n_digits: usize,
+    options: &DistributedOptions,
+    coordinator: &Coordinator,
+    output: &mut BlockOutput<impl FnMut(u64, &[u8]) -> io::Result<()>>,
+    block_len: usize,
+) -> io::Result<OutputManifest> {
+    let (raw_tx, raw_rx) = channel::<i32>();
+    let (block_tx, block_rx) = channel::<Vec<u8>>();
+    let corrector = thread::spawn(move || {
+        let mut digits = PiDigitsIter::new(raw_rx.into_iter()).map(|digit| b'0' + digit);
+        loop {
+            let block = digits.by_ref().take(block_len.max(1)).collect::<Vec<u8>>();
+            if block.is_empty() || block_tx.send(block).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut sink = FileSink { raw: raw_tx, blocks: &block_rx, output, error: None };
+    rank0_coordinator(transport, n_digits, options, &mut sink, coordinator);
+    let FileSink { raw, output, error, .. } = sink;
+
+    // Com o fim dos dígitos brutos, a correção libera os últimos blocos
+    drop(raw);
+    let written = match error {
+        Some(error) => Err(error),
+        None => block_rx.iter().try_for_each(|block| output.append(&block)),
+    };
+    corrector.join().expect("Thread de correção dos dígitos panicou");
+    written.map(|()| output.manifest())
+}
+
+/// Destino usado por `rank0_to_file`: repassa os dígitos brutos à thread de correção e
+/// grava os blocos que ela já devolveu
+#[cfg(any(feature = "mpi", test))]
+struct FileSink<'a, W> {
+    raw: Sender<i32>,
+    blocks: &'a Receiver<Vec<u8>>,
+    output: &'a mut BlockOutput<W>,
+    /// Primeiro erro de escrita. Depois dele nenhum dígito é aceito.
+    error: Option<io::Error>,
+}
+
+#[cfg(any(feature = "mpi", test))]
+impl<W: FnMut(u64, &[u8]) -> io::Result<()>> DigitSink for &mut FileSink<'_, W> {
+    fn accept(&mut self, digits: &[i32]) -> bool {
+        if self.error.is_some() || !self.raw.accept(digits) {
+            return false;
+        }
+        match self.blocks.try_iter().try_for_each(|block| self.output.append(&block)) {
+            Ok(()) => true,
+            Err(error) => {
+                self.error = Some(error);
+                false
+            }
+        }
+    }
+}
+
+/// Parte do rank 0 no pipeline: envia os triggers ao último rank dentro da janela de
+/// crédito, processa os lotes que chegam do rank 1 no chunk mais à esquerda (com o
+/// pipeline de threads local) e repassa os dígitos para `tx`.
+/// No cancelamento envia `Abort` a todos os workers e retorna; se um rank abortar,
+/// registra o motivo e retorna.
+fn pipeline<T: Transport>(
+    transport: &T,
+    n_digits: usize,
+    options: &DistributedOptions,
+    tx: &mut impl DigitSink,
+    coordinator: &Coordinator,
+) -> StageStats {
+    let size = transport.size();
+    let last_rank = size - 1;
+    let carry_batch = options.carry_batch.max(1);
+    let window = options.max_in_flight.max(carry_batch);
+
+    // O rank 0 fica com o chunk 0, o último a receber os carries
+    let total_len = (n_digits * 10) / 3;
+    let (start_global_index, range) = Partition::balanced(total_len, size as usize).part(0);
+    let mut local_array = init_cells(start_global_index..start_global_index + range.len());
+    let counters = local_counters(options);
+    let mut carries = 0;
+
+    // Verifica cancelamento e falhas em outros ranks. Retorna `true` se o rank 0 deve parar.
+    let should_stop = || {
+        if options.cancellation.is_cancelled() {
+            abort_all(transport, None);
+            return true;
+        }
+        if let Some(failure) = receive_abort(transport, options.receive_timeout) {
+            fail(coordinator, options, failure);
+            return true;
+        }
+        false
+    };
+
+    // Aborta os outros ranks e registra a falha
+    let abort = |failure| {
+        abort_all(transport, Some(failure));
+        fail(coordinator, options, failure);
+    };
+
+    with_local_pipeline(&mut local_array, start_global_index, options, &counters, |local| {
+        let mut sent = 0;
+        let mut next_checkpoint = CHECKPOINT_INTERVAL;
+        let mut terminate_sent = false;
+        let mut terminate_received = false;
+        // Última mensagem do rank 1 ou saída do pipeline local
+        let mut last_activity = Instant::now();
+        loop {
+            if should_stop() {
+                return;
+            }
+            if last_activity.elapsed() > options.receive_timeout {
+                abort(RankFailure::Timeout { rank: 1, timeout: options.receive_timeout });
+                return;
+            }
+
+            // 1. Envia triggers em lotes enquanto houver crédito: os dígitos em trânsito são
+            // os enviados que ainda não saíram do rank 0. Um `Checkpoint` segue a cada
+            // CHECKPOINT_INTERVAL carries.
+            while sent < n_digits {
+                let batch_len = (n_digits - sent).min(carry_batch);
+                if sent + batch_len - carries > window {
+                    break;
+                }
+                transport.send(last_rank, Tag::for_batch(batch_len), vec![0; batch_len]);
+                sent += batch_len;
+                if sent >= next_checkpoint && sent < n_digits {
+                    transport.send(last_rank, Tag::Checkpoint, vec![sent as i32]);
+                    next_checkpoint += CHECKPOINT_INTERVAL;
+                }
+            }
+
+            // 2. Finalização: o `Terminate` segue o pipeline atrás do último lote
+            if sent == n_digits && !terminate_sent {
+                transport.send(last_rank, Tag::Terminate, vec![0]);
+                terminate_sent = true;
+            }
+
+            // 3. Recebe as mensagens do rank 1 na ordem em que foram enviadas e as passa ao
+            // pipeline local. Este rank é o único que usa o transporte, então em vez de
+            // bloquear no receive ele verifica periodicamente se há uma mensagem pronta.
+            // Depois do `Terminate` só chegam as estatísticas, recebidas no fim.
+            receive_progress(transport, &coordinator.rank_idle);
+            let mut received = false;
+            let message = if terminate_received { None } else { transport.try_receive(Some(1), None) };
+            if let Some(message) = message {
+                match Tag::from_i32(message.tag) {
+                    Some(Tag::Progress) => record_progress(&coordinator.rank_idle, &message),
+                    Some(Tag::Abort) => {
+                        let failure = RankFailure::decode(&message.data, options.receive_timeout);
+                        fail(coordinator, options, failure.unwrap_or(RankFailure::Rank(1)));
+                        return;
+                    }
+                    Some(tag @ (Tag::Carry | Tag::CarryBatch | Tag::Checkpoint | Tag::Terminate)) => {
+                        let _ = local.input.send((tag, message.data));
+                        received = true;
+                        terminate_received = tag == Tag::Terminate;
+                        last_activity = Instant::now();
+                    }
+                    // O rank 1 não envia outras tags ao rank 0 antes do `Terminate`
+                    _ => {
+                        abort(RankFailure::Rank(0));
+                        return;
+                    }
+                }
+            }
+
+            // 4. Os dígitos que saem do pipeline local vão para o iterador via channel
+            let timeout = if received { Duration::ZERO } else { CANCELLATION_POLL_INTERVAL };
+            match local.output.recv_timeout(timeout) {
+                Ok((Tag::Carry | Tag::CarryBatch, digits)) => {
+                    carries += digits.len();
+                    last_activity = Instant::now();
+                    coordinator.rank_idle.lock().unwrap()[0] = local_stats(&counters).recv_blocked;
+
+                    // Se o destino (o iterador na main thread) fechou, paramos de processar
+                    if !tx.accept(&digits) {
+                        options.cancellation.cancel();
+                    }
+                }
+                Ok((Tag::Checkpoint, checkpoint)) if checkpoint == [carries as i32] => {}
+                Ok((Tag::Terminate, _)) => break,
+                Err(RecvTimeoutError::Timeout) => {}
+                // Checkpoint divergente ou falha em um estágio local
+                Ok(_) | Err(RecvTimeoutError::Disconnected) => {
+                    abort(RankFailure::Rank(0));
+                    return;
+                }
+            }
+        }
+        receive_progress(transport, &coordinator.rank_idle);
+    });
+
+    StageStats { carries: carries as u64, ..local_stats(&counters) }
+}
+
+/// Registra a primeira falha e interrompe o iterador
+fn fail(coordinator: &Coordinator, options: &DistributedOptions, failure: RankFailure) {
+    coordinator.failure.lock().unwrap().get_or_insert(failure);
+    options.cancellation.cancel();
+}
+
+/// Processa um lote no chunk local. Um panic (por exemplo, overflow de uma célula) vira
+/// `false`, para que o rank avise os demais com `Abort` em vez de deixá-los esperando.
+fn process_batch(chunk: &mut LocalChunk, carries: &mut [i32], tile_len: usize) -> bool {
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        process_chunk_wavefront(chunk.cells, chunk.dens, chunk.start_global_index, carries, tile_len)
+    }))
+    .is_ok()
+}
+
+/// Registra um relatório de tempo ocioso de um worker
+fn record_progress(rank_idle: &Mutex<Vec<Duration>>, message: &Message) {
+    if let ([idle_ns], Some(idle)) = (&decode_u64s(&message.data)[..], rank_idle.lock().unwrap().get_mut(message.source as usize)) {
+        *idle = Duration::from_nanos(*idle_ns);
+    }
+}
+
+/// Recebe os relatórios de tempo ocioso que já chegaram dos workers
+fn receive_progress<T: Transport>(transport: &T, rank_idle: &Mutex<Vec<Duration>>) {
+    while let Some(message) = transport.try_receive(None, Some(Tag::Progress)) {
+        record_progress(rank_idle, &message);
+    }
+}
+
+/// Recebe um `Abort` pendente de qualquer rank, retornando o motivo. Sem um motivo
+/// reconhecido, a falha é atribuída ao rank de origem.
+fn receive_abort<T: Transport>(transport: &T, timeout: Duration) -> Option<RankFailure> {
+    let message = transport.try_receive(None, Some(Tag::Abort))?;
+    Some(RankFailure::decode(&message.data, timeout).unwrap_or(RankFailure::Rank(message.source)))
+}
+
+/// Recebe as estatísticas finais de cada worker (na ordem `busy`, `recv_blocked`,
+/// `send_blocked` em ns e `carries`). Os ranks que não enviarem dentro de `timeout` (por
+/// exemplo, um processo morto) ficam com as estatísticas zeradas, assim como `dead_rank`,
+/// que já se sabe que não responde e não é esperado.
+fn receive_stats<T: Transport>(transport: &T, timeout: Duration, stats: &Mutex<Vec<StageStats>>, dead_rank: Option<i32>) {
+    let deadline = Instant::now() + timeout;
+    for rank in (1..transport.size()).filter(|&rank| Some(rank) != dead_rank) {
+        let message = loop {
+            if let Some(message) = transport.try_receive(Some(rank), Some(Tag::Stats)) {
+                break Some(message);
+            }
+            if Instant::now() >= deadline {
+                break None;
+            }
+            thread::sleep(CANCELLATION_POLL_INTERVAL);
+        };
+        let Some(message) = message else { continue };
+        if let [busy, recv_blocked, send_blocked, carries] = decode_u64s(&message.data)[..] {
+            stats.lock().unwrap()[rank as usize] = StageStats {
+                busy: Duration::from_nanos(busy),
+                recv_blocked: Duration::from_nanos(recv_blocked),
+                send_blocked: Duration::from_nanos(send_blocked),
+                carries,
+                ..StageStats::default()
+            };
+        }
+    }
+}
+
+/// Envia `Abort` diretamente a todos os outros ranks, que param sem processar os lotes
+/// pendentes. Sem `failure`, o `Abort` é um cancelamento.
+fn abort_all<T: Transport>(transport: &T, failure: Option<RankFailure>) {
+    let rank = transport.rank();
+    for other in (0..transport.size()).filter(|&other| other != rank) {
+        transport.send(other, Tag::Abort, RankFailure::encode(failure));
+    }
+}
+
+/// Mensagem do pipeline de threads de um rank: a tag com que chegou e os dados
+type LocalMessage = (Tag, Vec<i32>);
+
+/// Ligações do pipeline de threads de um rank com a thread que usa o transporte
+struct LocalPipeline {
+    /// Entrada do estágio mais à direita
+    input: Sender<LocalMessage>,
+    /// Saída do estágio mais à esquerda
+    output: Receiver<LocalMessage>,
+}
+
+/// Contadores de cada estágio do pipeline de threads de um rank
+fn local_counters(options: &DistributedOptions) -> Vec<StageCounters> {
+    (0..options.num_threads.max(1)).map(|_| StageCounters::default()).collect()
+}
+
+/// Estatísticas somadas dos estágios do pipeline de threads de um rank
+fn local_stats(counters: &[StageCounters]) -> StageStats {
+    PipelineStats { stages: counters.iter().map(StageCounters::snapshot).collect() }.total()
+}
+
+/// Executa `body` com o pipeline de threads do rank rodando sobre `cells`.
+///
+/// O chunk local é dividido com `BalancedChunksMut` em `num_threads` partes, uma por
+/// estágio, ligadas da direita para a esquerda como no `calculate_pi_parallel`. Mensagens
+/// de controle atravessam os estágios sem alteração, então saem na mesma posição em
+/// relação aos lotes. Se um estágio falhar, um `Abort` sai no lugar do lote.
+///
+/// Os canais não têm limite: a janela de crédito do rank 0 já limita os dígitos em
+/// trânsito, e assim a thread do transporte nunca bloqueia ao entregar uma mensagem.
+/// Quando `body` retorna, a entrada é fechada e os estágios terminam sem processar o que
+/// sobrou.
+fn with_local_pipeline(
+    cells: &mut [i32],
+    start_global_index: usize,
+    options: &DistributedOptions,
+    counters: &[StageCounters],
+    body: impl FnOnce(&LocalPipeline),
+) {
+    let stop = CancellationToken::new();
+    let dens = &init_denominators(start_global_index..start_global_index + cells.len())[..];
+    let partition = Partition::balanced(cells.len(), counters.len()).with_offset(start_global_index);
+
+    thread::scope(|scope| {
+        let (input, mut output) = channel();
+        let stages = BalancedChunksMut::from_partition(cells, partition).with_global_starts().enumerate().rev();
+        for (i, (global_start, chunk)) in stages {
+            let (tx, next_output) = channel();
+            let rx = mem::replace(&mut output, next_output);
+            let (counters, stop) = (&counters[i], &stop);
+            let dens = &dens[global_start - start_global_index..][..chunk.len()];
+            let chunk = LocalChunk { cells: chunk, dens, start_global_index: global_start };
+            scope.spawn(move || run_local_stage(chunk, rx, tx, counters, options.tile_len, stop));
+        }
+
+        let pipeline = LocalPipeline { input, output };
+        body(&pipeline);
+        stop.cancel();
+        drop(pipeline);
+    });
+}
+
+/// Parte do chunk local processada por um estágio do pipeline de threads de um rank
+struct LocalChunk<'a> {
+    cells: &'a mut [i32],
+    dens: &'a [Reciprocal],
+    start_global_index: usize,
+}
+
+/// Laço de um estágio do pipeline de threads de um rank
+fn run_local_stage(
+    mut chunk: LocalChunk,
+    rx: Receiver<LocalMessage>,
+    tx: Sender<LocalMessage>,
+    counters: &StageCounters,
+    tile_len: usize,
+    stop: &CancellationToken,
+) {
+    loop {
+        let waiting_since = Instant::now();
+        let Ok((tag, mut carries)) = rx.recv() else { break };
+        StageCounters::add(&counters.recv_wait_ns, waiting_since.elapsed());
+        if stop.is_cancelled() {
+            break;
+        }
+
+        if matches!(tag, Tag::Carry | Tag::CarryBatch) {
+            let busy_since = Instant::now();
+            if !process_batch(&mut chunk, &mut carries, tile_len) {
+                let _ = tx.send((Tag::Abort, Vec::new()));
+                break;
+            }
+            StageCounters::add(&counters.busy_ns, busy_since.elapsed());
+            counters.carries.fetch_add(carries.len() as u64, Ordering::Relaxed);
+        }
+
+        // O `Terminate` é a última mensagem: depois dele a entrada só espera ser fechada
+        if tx.send((tag, carries)).is_err() || tag == Tag::Terminate {
+            break;
+        }
+    }
+}
+
+/// Parte dos ranks 1..N: processam chunks em pipeline.
+///
+/// A thread do transporte só comunica: as mensagens recebidas passam pelo pipeline de
+/// threads do rank e o que sai dele é enviado ao rank anterior. Os envios podem terminar
+/// em segundo plano, então o resultado anterior ainda está sendo enviado enquanto os
+/// estágios calculam.
+///
+/// Retorna o motivo do `Abort`, se a computação foi abortada por uma falha.
+pub(crate) fn rank_worker<T: Transport>(transport: &T, n_digits: usize, options: &DistributedOptions) -> Option<RankFailure> {
+    let rank = transport.rank();
+    let size = transport.size();
+
+    // Calcular tamanho total do array
+    let total_len = (n_digits * 10) / 3;
+
+    // Divisão de trabalho entre todos os ranks (o rank 0 fica com o chunk mais à esquerda),
+    // a mesma usada pelo pipeline de threads
+    let (start_global_index, range) = Partition::balanced(total_len, size as usize).part(rank as usize);
+
+    // Criar array local (os recíprocos dos denominadores ficam em `with_local_pipeline`)
+    let mut local_array = init_cells(start_global_index..start_global_index + range.len());
+
+    let counters = local_counters(options);
+    let mut carries = 0u64;
+    let mut send_blocked = Duration::ZERO;
+    let prev_rank = rank - 1;
+    let next_rank = if rank == size - 1 { 0 } else { rank + 1 };
+    let timeout = options.receive_timeout;
+    let mut failure = None;
+
+    with_local_pipeline(&mut local_array, start_global_index, options, &counters, |local| {
+        let mut aborted = false;
+        let mut last_report = Instant::now();
+        // Última mensagem recebida ou saída do pipeline local
+        let mut last_activity = Instant::now();
+
+        // Aborta os outros ranks, retornando a falha
+        let abort = |reason| {
+            abort_all(transport, Some(reason));
+            Some(reason)
+        };
+
+        // Loop de comunicação
+        loop {
+            // 1. Uma mensagem recebida entra no pipeline local. Recebe do rank "acima" (ou
+            // 0 se for o último) sem filtrar a tag, para que lotes e mensagens de controle
+            // em banda cheguem na ordem em que foram enviados. Qualquer origem é aceita
+            // para que um `Abort` de outro rank também chegue.
+            let mut received = false;
+            if let Some(message) = transport.try_receive(None, None) {
+                received = true;
+                last_activity = Instant::now();
+                match Tag::from_i32(message.tag) {
+                    Some(Tag::Abort) => {
+                        failure = RankFailure::decode(&message.data, timeout);
+                        aborted = true;
+                        break;
+                    }
+                    Some(tag @ (Tag::Carry | Tag::CarryBatch | Tag::Checkpoint | Tag::Terminate)) => {
+                        let _ = local.input.send((tag, message.data));
+                    }
+                    // Tag desconhecida ou fora do protocolo: os demais ranks não teriam como
+                    // continuar em sincronia
+                    _ => {
+                        failure = abort(RankFailure::Rank(rank));
+                        aborted = true;
+                        break;
+                    }
+                }
+            }
+
+            // Sem notícias do rank acima, ele provavelmente morreu: sem o `Abort`, os
+            // demais ranks ficariam esperando para sempre
+            if last_activity.elapsed() > timeout {
+                failure = abort(RankFailure::Timeout { rank: next_rank, timeout });
+                aborted = true;
+                break;
+            }
+
+            // 2. O que sai do pipeline local segue para o rank anterior (o rank 0 também
+            // processa). Mensagens para o mesmo destino não se ultrapassam, então o controle
+            // fica atrás dos lotes ainda em envio.
+            let poll = if received { Duration::ZERO } else { CANCELLATION_POLL_INTERVAL };
+            match local.output.recv_timeout(poll) {
+                Ok((tag @ (Tag::Carry | Tag::CarryBatch), message)) => {
+                    carries += message.len() as u64;
+                    last_activity = Instant::now();
+
+                    // Envia depois que o envio anterior terminar. Um envio que não termina
+                    // indica que o rank anterior parou de receber.
+                    let sending_since = Instant::now();
+                    let flushed = transport.flush(timeout);
+                    send_blocked += sending_since.elapsed();
+                    if !flushed {
+                        failure = abort(RankFailure::Timeout { rank: prev_rank, timeout });
+                        aborted = true;
+                        break;
+                    }
+                    transport.send(prev_rank, tag, message);
+                }
+                Ok((Tag::Checkpoint, message)) if message == [carries as i32] => {
+                    transport.send(prev_rank, Tag::Checkpoint, message);
+                }
+                Ok((Tag::Terminate, message)) => {
+                    // Repassar o fim dos dados para o rank anterior, atrás do último lote
+                    transport.send(prev_rank, Tag::Terminate, message);
+                    break;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                // Checkpoint divergente ou falha em um estágio local
+                Ok(_) | Err(RecvTimeoutError::Disconnected) => {
+                    failure = abort(RankFailure::Rank(rank));
+                    aborted = true;
+                    break;
+                }
+            }
+
+            if last_report.elapsed() >= PROGRESS_REPORT_INTERVAL {
+                last_report = Instant::now();
+                let idle = local_stats(&counters).recv_blocked;
+                transport.send(0, Tag::Progress, encode_u64s(&[idle.as_nanos() as u64]));
+            }
+        }
+
+        // Os últimos envios só são aguardados num término normal: depois de um `Abort` o
+        // rank anterior pode não recebê-los mais
+        if !aborted && !transport.flush(timeout) {
+            failure = abort(RankFailure::Timeout { rank: prev_rank, timeout });
+        }
+    });
+
+    let stats = local_stats(&counters);
+    let report = [
+        stats.busy.as_nanos() as u64,
+        stats.recv_blocked.as_nanos() as u64,
+        (stats.send_blocked + send_blocked).as_nanos() as u64,
+        carries,
+    ];
+    transport.send(0, Tag::Stats, encode_u64s(&report));
+    if !matches!(failure, Some(RankFailure::Timeout { .. })) {
+        transport.flush(timeout);
+    }
+    failure
+}
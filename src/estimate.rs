@@ -0,0 +1,206 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+use crate::distributed::DEFAULT_MAX_IN_FLIGHT;
+use crate::parallel_pi::ParallelOptions;
+use crate::reciprocal::Reciprocal;
+use crate::spigot_cell::{init_cells, init_denominators, process_chunk_wavefront, CELL_BYTES};
+use crate::task_pipeline::TaskOptions;
+
+/// Limite de memória de cada pod em `spigot-job.yaml` (512Mi)
+pub const POD_MEMORY_LIMIT: u64 = 512 * 1024 * 1024;
+
+/// Limite do valor intermediário de uma célula, em múltiplos do tamanho do array.
+///
+/// Na célula `i > 0` o valor é o resto de uma divisão por `2i + 1`, no máximo `2i`, e o
+/// carry que chega da direita nunca passa de 20: a última célula recebe carry 0 e, se a
+/// célula `i + 1` recebe no máximo 20, o quociente que ela repassa é no máximo
+/// `(10 * 2(i + 1) + 20 * (i + 2)) / (2i + 3) = 20`. Então
+/// `valor * 10 + carry * (i + 1) <= 20i + 20(i + 1) = 40i + 20 < 40 * len`. A célula 0
+/// (denominador 10) fica em no máximo `9 * 10 + 20`.
+const MAX_CELL_VALUE_FACTOR: u64 = 40;
+
+/// Células usadas no benchmark de calibração
+const CALIBRATION_CELLS: usize = 64 * 1024;
+
+/// Carries usados no benchmark de calibração
+const CALIBRATION_CARRIES: usize = 16;
+
+/// Backend para o qual a estimativa é feita
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// `calculate_pi_sequential`
+    Sequential,
+    /// `calculate_pi_parallel`, com uma thread por estágio
+    Parallel,
+    /// `calculate_pi_tasks` (e `calculate_pi_rayon`), com as opções padrão
+    Tasks,
+    /// `calculate_pi_mpi`, com um chunk por rank (o rank 0 também processa). Com um único
+    /// rank, o rank 0 calcula tudo com o pipeline de threads.
+    Mpi,
+}
+
+/// Tipo de inteiro necessário para as células
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CellType {
+    I32,
+    /// Os valores intermediários não cabem em `i32`: os engines atuais causam panic por
+    /// overflow nessa faixa
+    I64,
+}
+
+/// Velocidade do kernel medida na máquina local
+#[derive(Debug, Clone, Copy)]
+pub struct Calibration {
+    /// Tempo médio de uma atualização de célula
+    pub ns_per_cell_update: f64,
+}
+
+impl Calibration {
+    /// Mede a velocidade do kernel em frente de onda com um benchmark curto (alguns ms)
+    pub fn measure() -> Self {
+        let mut cells = init_cells(0..CALIBRATION_CELLS);
+        let dens = init_denominators(0..CALIBRATION_CELLS);
+        let tile_len = ParallelOptions::default().tile_len;
+
+        // Uma rodada para aquecer o cache antes de medir
+        process_chunk_wavefront(&mut cells, &dens, 0, &mut [0; CALIBRATION_CARRIES], tile_len);
+        let start = Instant::now();
+        process_chunk_wavefront(&mut cells, &dens, 0, &mut [0; CALIBRATION_CARRIES], tile_len);
+        let elapsed = start.elapsed();
+
+        let updates = (CALIBRATION_CELLS * CALIBRATION_CARRIES) as f64;
+        Self { ns_per_cell_update: elapsed.as_nanos() as f64 / updates }
+    }
+}
+
+/// Estimativa de recursos de uma execução
+#[derive(Debug, Clone)]
+pub struct Estimate {
+    pub backend: Backend,
+    /// Número de células do array
+    pub array_len: usize,
+    /// Número de partes em que o array é dividido (estágios ou ranks)
+    pub num_parts: usize,
+    /// Bytes do maior chunk (ou do chunk de cada rank)
+    pub bytes_per_chunk: u64,
+    /// Bytes dos buffers de comunicação no pior caso (no MPI, no rank que mais acumula)
+    pub channel_bytes: u64,
+    /// Memória total do processo (ou, no MPI, do rank que mais usa)
+    pub bytes_per_process: u64,
+    /// Maior valor intermediário esperado em uma célula
+    pub max_cell_value: u64,
+    pub cell_type: CellType,
+    /// Tempo estimado da execução, a partir da calibração
+    pub time: Duration,
+    /// Problemas encontrados na configuração
+    pub warnings: Vec<String>,
+}
+
+impl Estimate {
+    /// Retorna `true` se a execução cabe nos limites de cada pod de `spigot-job.yaml`
+    pub fn fits_pod_limit(&self) -> bool {
+        self.bytes_per_process <= POD_MEMORY_LIMIT
+    }
+}
+
+/// Estima memória e tempo de uma execução, calibrando com um benchmark local curto.
+///
+/// # Parâmetros
+/// - `n_digits`: Número de dígitos de PI
+/// - `backend`: Backend usado
+/// - `workers`: Número de threads (ou de estágios) nos backends com threads, ou de ranks
+///   (incluindo o rank 0) no MPI. Ignorado no sequencial.
+pub fn estimate(n_digits: usize, backend: Backend, workers: usize) -> Estimate {
+    estimate_with_calibration(n_digits, backend, workers, Calibration::measure())
+}
+
+/// Igual a `estimate`, mas com uma calibração já medida (por exemplo em outra máquina)
+pub fn estimate_with_calibration(n_digits: usize, backend: Backend, workers: usize, calibration: Calibration) -> Estimate {
+    if backend == Backend::Mpi && workers <= 1 {
+        let threads = ParallelOptions::default().num_threads;
+        return Estimate { backend, ..estimate_with_calibration(n_digits, Backend::Parallel, threads, calibration) };
+    }
+
+    let array_len = n_digits * 10 / 3;
+    // Valor da célula mais o recíproco do denominador
+    let cell_bytes = CELL_BYTES as u64;
+    let carry_bytes = size_of::<i32>() as u64;
+    let vec_bytes = size_of::<Vec<i32>>() as u64;
+    let mut warnings = Vec::new();
+
+    let (num_parts, channel_bytes) = match backend {
+        Backend::Sequential => (1, 0),
+        Backend::Parallel => {
+            let options = ParallelOptions::default();
+            let stages = workers.max(1);
+            // Cada ligação (triggers + saída de cada estágio) pode ficar cheia, com lotes
+            // juntados até a profundidade da frente de onda
+            let message = vec_bytes + options.carry_batch.max(options.wavefront_depth) as u64 * carry_bytes;
+            (stages, (stages as u64 + 1) * options.channel_bound as u64 * message)
+        }
+        Backend::Tasks => {
+            let options = TaskOptions::default();
+            let stages = workers.max(1);
+            let message = vec_bytes + options.carry_batch as u64 * carry_bytes;
+            (stages, options.max_in_flight as u64 * message)
+        }
+        Backend::Mpi => {
+            // No pior caso toda a janela de crédito (`MpiOptions::max_in_flight`) fica nos
+            // buffers de um único rank
+            (workers, n_digits.min(DEFAULT_MAX_IN_FLIGHT) as u64 * carry_bytes)
+        }
+    };
+
+    let largest_chunk = array_len.div_ceil(num_parts);
+    let bytes_per_chunk = largest_chunk as u64 * cell_bytes;
+    let bytes_per_process = match backend {
+        // Array de `Option<Cell<i32>>` mais a tabela de recíprocos
+        Backend::Sequential => array_len as u64 * (size_of::<Option<Cell<i32>>>() + size_of::<Reciprocal>()) as u64,
+        Backend::Parallel | Backend::Tasks => array_len as u64 * cell_bytes + channel_bytes,
+        Backend::Mpi => bytes_per_chunk + channel_bytes,
+    };
+
+    let max_cell_value = array_len as u64 * MAX_CELL_VALUE_FACTOR;
+    let cell_type = match max_cell_value <= i32::MAX as u64 {
+        true => CellType::I32,
+        false => CellType::I64,
+    };
+    if cell_type == CellType::I64 {
+        warnings.push(format!(
+            "{} dígitos exigem células i64 (valor máximo ~{}), mas os engines usam i32",
+            n_digits, max_cell_value
+        ));
+    }
+    if bytes_per_process > POD_MEMORY_LIMIT {
+        warnings.push(format!(
+            "{:.1} MiB por processo excedem o limite de 512Mi de spigot-job.yaml",
+            bytes_per_process as f64 / (1024.0 * 1024.0)
+        ));
+    }
+
+    // No pipeline cada dígito passa por todos os estágios, mas os estágios trabalham ao
+    // mesmo tempo: o tempo é ditado pelo maior chunk, mais o preenchimento do pipeline.
+    // Backends com threads não ganham com mais threads do que cores.
+    let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let effective_parts = match backend {
+        Backend::Sequential => 1,
+        Backend::Parallel | Backend::Tasks => num_parts.min(cores),
+        Backend::Mpi => num_parts,
+    };
+    let updates_per_part = n_digits as f64 * array_len.div_ceil(effective_parts) as f64;
+    let fill = (effective_parts - 1) as f64 * largest_chunk as f64;
+    let time = Duration::from_secs_f64((updates_per_part + fill) * calibration.ns_per_cell_update / 1e9);
+
+    Estimate {
+        backend,
+        array_len,
+        num_parts,
+        bytes_per_chunk,
+        channel_bytes,
+        bytes_per_process,
+        max_cell_value,
+        cell_type,
+        time,
+        warnings,
+    }
+}
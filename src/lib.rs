@@ -1,12 +1,32 @@
 pub mod balanced_chunks_mut;
+pub mod cancellation;
+pub mod distributed;
+pub mod estimate;
+pub mod output;
+pub mod parallel_pi;
+pub mod partition;
 pub mod pi_digits_iter;
+pub mod progress;
+pub mod reciprocal;
+pub mod socket_pi;
+pub mod spigot_cell;
+pub mod spsc;
+pub mod stats;
+pub mod task_pipeline;
+pub mod transport;
 
 #[cfg(feature = "mpi")]
 pub mod mpi_pi;
 
-use std::{cell::Cell, iter, sync::mpsc::{channel, sync_channel, Receiver}, thread};
-use balanced_chunks_mut::BalancedChunksMut;
+#[cfg(feature = "rayon")]
+pub mod rayon_pi;
+
+#[cfg(feature = "async")]
+pub mod async_pi;
+
+use std::{cell::Cell, iter};
 use pi_digits_iter::PiDigitsIter;
+use reciprocal::Reciprocal;
 
 /// Calcula o denominador para o índice i no algoritmo Spigot
 #[inline]
@@ -32,9 +52,15 @@ pub fn calculate_pi_sequential(n_digits: usize) -> impl Iterator<Item = u8> {
     // por 10 então irei inicializar todos em 20 e deixar a multiplicação por 10 no final de cada
     // etapa.
     let arr = iter::once(None)
-        .chain(iter::repeat(Some(Cell::new(20))).take(n_digits * 10 / 3))
+        .chain(iter::repeat_n(Some(Cell::new(20)), n_digits * 10 / 3))
         .collect::<Vec<Option<Cell<i32>>>>();
 
+    // Tabela com o recíproco de den(i) para cada posição, trocando as divisões do laço
+    // interno por multiplicações
+    let dens = (0..n_digits * 10 / 3)
+        .map(|i| Reciprocal::new(den(i as i32) as u32))
+        .collect::<Vec<Reciprocal>>();
+
     // Loop para cada dígito de PI
     let pi_digits_raw = (0..n_digits).map(move |_| {
         let mut digit = -1;
@@ -55,11 +81,11 @@ pub fn calculate_pi_sequential(n_digits: usize) -> impl Iterator<Item = u8> {
             };
 
             // Com isso podemos fazer a divisão pelo denominador.
-            let resto = curr_cell.get() % den(i as i32);
-            let div = curr_cell.get() / den(i as i32);
+            let (div, resto) = dens[i].div_rem(curr_cell.get() as u32);
+            let div = div as i32;
 
             // e podemos ajustar o valor atual do array
-            curr_cell.set(resto);
+            curr_cell.set(resto as i32);
 
             if let Some(next) = next {
                 // Caso ainda exista um "próximo" elemento, temos que atualizar ele também
@@ -92,114 +118,32 @@ pub fn calculate_pi_sequential(n_digits: usize) -> impl Iterator<Item = u8> {
     PiDigitsIter::new(pi_digits_raw)
 }
 
-/// Calcula os dígitos de PI usando o algoritmo Spigot de forma paralela
-/// 
-/// # Parâmetros
-/// - `n_digits`: Número de dígitos de PI a serem calculados
-/// - `num_threads`: Número de threads para processamento paralelo
-/// - `channel_bound`: Tamanho do buffer dos canais de comunicação entre threads
-/// 
-/// # Retorna
-/// Um iterador sobre os dígitos de PI (u8) que não bloqueia a thread principal
-pub fn calculate_pi_parallel(n_digits: usize, num_threads: usize, channel_bound: usize) -> impl Iterator<Item = u8> {
-    // Cálculo exato do tamanho do array
-    let total_len = (n_digits * 10) / 3;
-    let mut big_array = vec![2; total_len];
-
-    // Cria um canal síncrono para disparar o processamento de cada dígito
-    let (trigger_tx, trigger_rx) = sync_channel::<i32>(channel_bound);
-
-    // Thread que dispara o processamento de cada dígito
-    thread::spawn(move || {
-        for _ in 0..n_digits {
-            if trigger_tx.send(0).is_err() {
-                break;
-            }
-        }
-    });
-
-    // Canal para receber o último rx da última thread criada
-    let (last_rx_tx, last_rx_rx) = channel::<Receiver<i32>>();
-
-    // Thread que processa os dados e envia os dados brutos para o canal
-    thread::spawn(move || {
-        thread::scope(|scope| {
-            let mut input_source = trigger_rx;
-
-            let base_size = total_len / num_threads;
-            let remainder = total_len % num_threads;
-
-            // Divide o array em chunks balanceados e processa cada chunk em uma thread separada
-            for (i, chunk) in BalancedChunksMut::new(&mut big_array, num_threads).enumerate().rev() {
-                let start_global_index = (base_size * i) + i.min(remainder);
-
-                let (tx, rx) = channel();
-                let rx_in = input_source;
-                input_source = rx;
-
-                scope.spawn(move || {
-                    for carry_in in rx_in {
-                        // fold consome o iterador reverso do chunk (processando da direita para a esquerda)
-                        let carry_out = chunk
-                            .iter_mut()
-                            .enumerate()
-                            .rev()
-                            .fold(carry_in, |carry, (idx, cell)| {
-                                let global_idx: i32 = start_global_index
-                                    .checked_add(idx)
-                                    .and_then(|x: usize| x.try_into().ok())
-                                    .expect("Overflow ao calcular global_idx");
-                                
-                                let cell_value: i32 = *cell;
-                                let cell_x10: i32 = cell_value
-                                    .checked_mul(10i32)
-                                    .expect("Overflow ao multiplicar cell por 10");
-                                
-                                let global_idx_plus_one: i32 = global_idx
-                                    .checked_add(1i32)
-                                    .expect("Overflow ao adicionar 1 a global_idx");
-                                
-                                let carry_x_idx: i32 = carry
-                                    .checked_mul(global_idx_plus_one)
-                                    .expect("Overflow ao multiplicar carry por (global_idx + 1)");
-                                
-                                let current = cell_x10
-                                    .checked_add(carry_x_idx)
-                                    .expect("Overflow ao calcular current");
-                                
-                                let denominator = den(global_idx);
-                                
-                                *cell = current
-                                    .checked_rem(denominator)
-                                    .expect("Overflow ou divisão por zero no resto");
-                                
-                                current
-                                    .checked_div(denominator)
-                                    .expect("Overflow ou divisão por zero na divisão")
-                            });
-
-                        if tx.send(carry_out).is_err() {
-                            break;
-                        }
-                    }
-                });
-            }
-
-            // Envia o último rx (input_source) através do canal
-            let _ = last_rx_tx.send(input_source);
-        });
-    });
-
-    // Recebe o último rx da thread
-    let final_rx = last_rx_rx.recv().unwrap();
-    
-    // Cria o PiDigitsIter na thread principal usando o último rx recebido
-    PiDigitsIter::new(final_rx.into_iter())
+/// Igual a `calculate_pi_sequential`, mas o iterador termina assim que `cancellation` for
+/// cancelado. Como o cálculo é feito sob demanda, descartar o iterador já libera o array.
+pub fn calculate_pi_sequential_cancellable(n_digits: usize, cancellation: CancellationToken) -> impl Iterator<Item = u8> {
+    calculate_pi_sequential(n_digits).take_while(move |_| !cancellation.is_cancelled())
 }
 
+pub use cancellation::CancellationToken;
+pub use distributed::{calculate_pi_in_memory, DistributedDigits, DistributedOptions, RankFailure};
+pub use estimate::{estimate, estimate_with_calibration, Backend, Calibration, CellType, Estimate};
+pub use output::{fnv1a64, OutputManifest};
+pub use parallel_pi::{calculate_pi_parallel, calculate_pi_parallel_with, ParallelDigits, ParallelOptions};
+pub use progress::{with_progress, CostModel, Progress, ProgressHook};
+pub use socket_pi::{run_socket_worker, SocketAddress, SocketCoordinator};
+pub use stats::{PipelineStats, StageStats};
+pub use task_pipeline::{calculate_pi_tasks, calculate_pi_with_spawner, Spawner, TaskOptions, WorkerPool};
+pub use transport::{InMemoryTransport, Message, Tag, Transport};
+
 // Re-exportar função MPI para uso externo (apenas quando a feature mpi estiver habilitada)
 #[cfg(feature = "mpi")]
-pub use mpi_pi::calculate_pi_mpi;
+pub use mpi_pi::{calculate_pi_mpi, calculate_pi_mpi_to_file, calculate_pi_mpi_with, MpiFailure, MpiOptions};
+
+#[cfg(feature = "rayon")]
+pub use rayon_pi::{calculate_pi_rayon, calculate_pi_rayon_with};
+
+#[cfg(feature = "async")]
+pub use async_pi::{into_stream, DigitStream};
 
 #[cfg(test)]
 mod tests;
\ No newline at end of file
@@ -5,198 +5,209 @@
 #[cfg(feature = "mpi")]
 use mpi::traits::*;
 #[cfg(feature = "mpi")]
-use std::sync::mpsc::{channel, Sender, IntoIter};
+use mpi::ffi;
 #[cfg(feature = "mpi")]
-use std::thread::{self, JoinHandle};
+use std::cell::RefCell;
 #[cfg(feature = "mpi")]
-use crate::pi_digits_iter::PiDigitsIter;
+use std::ffi::CString;
+#[cfg(feature = "mpi")]
+use std::io;
+#[cfg(feature = "mpi")]
+use std::mem;
+#[cfg(feature = "mpi")]
+use std::os::raw::c_int;
+#[cfg(feature = "mpi")]
+use std::path::Path;
+#[cfg(feature = "mpi")]
+use std::sync::mpsc::channel;
+#[cfg(feature = "mpi")]
+use std::thread;
+#[cfg(feature = "mpi")]
+use std::time::{Duration, Instant};
+#[cfg(feature = "mpi")]
+use crate::distributed::{
+    rank0_coordinator, rank0_to_file, rank_worker, Coordinator, DistributedDigits, DistributedOptions, RankFailure, CANCELLATION_POLL_INTERVAL,
+};
+#[cfg(feature = "mpi")]
+use crate::output::{BlockOutput, OutputManifest};
+#[cfg(feature = "mpi")]
+use crate::transport::{Message, Tag, Transport};
 // AI_GENERATED_CODE_END
 
 #[cfg(feature = "mpi")]
-/// Tags MPI para comunicação entre ranks
-#[derive(Debug, Clone, Copy)]
-enum MpiTag {
-    Carry = 0,
-}
+/// Dígitos de cada escrita de `calculate_pi_mpi_to_file`
+const OUTPUT_BLOCK_LEN: usize = 64 * 1024;
 
 #[cfg(feature = "mpi")]
-impl MpiTag {
-    fn as_i32(self) -> i32 {
-        self as i32
-    }
-}
+/// Opções de execução do pipeline MPI
+pub type MpiOptions = DistributedOptions;
 
 #[cfg(feature = "mpi")]
-/// Struct Wrapper (Guard) para garantir que a thread MPI finalize corretamente.
-///
-/// CORREÇÃO:
-/// Como PiDigitsIter é genérico <I>, precisamos especificar o tipo concreto do iterador.
-/// O channel::Receiver::into_iter() retorna o tipo 'std::sync::mpsc::IntoIter<i32>'.
-pub struct MpiGuardIter {
-    inner: PiDigitsIter<IntoIter<i32>>,
-    thread_handle: Option<JoinHandle<()>>,
+/// Motivo pelo qual uma computação MPI foi abortada
+pub type MpiFailure = RankFailure;
+
+#[cfg(feature = "mpi")]
+/// Iterador do rank 0 sobre os dígitos calculados com MPI. Garante que a thread MPI
+/// finalize corretamente (com `MPI_Finalize`) quando termina ou é descartado.
+pub type MpiGuardIter = DistributedDigits;
+
+#[cfg(feature = "mpi")]
+/// `Transport` sobre um comunicador MPI. Os envios usam `MPI_Isend` e ficam pendentes até
+/// terminarem; os receives usam probe seguido de receive, sem bloquear.
+struct MpiTransport<'a, C> {
+    world: &'a C,
+    pending: RefCell<Vec<PendingRequest>>,
 }
 
 #[cfg(feature = "mpi")]
-impl Iterator for MpiGuardIter {
-    type Item = u8;
+impl<'a, C: Communicator + AsRaw<Raw = ffi::MPI_Comm>> MpiTransport<'a, C> {
+    fn new(world: &'a C) -> Self {
+        Self { world, pending: RefCell::new(Vec::new()) }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.inner.next()
+    /// Descarta os envios que já terminaram, retornando quantos ainda estão pendentes
+    fn reap(&self) -> usize {
+        let mut pending = self.pending.borrow_mut();
+        pending.retain_mut(|request| !request.test());
+        pending.len()
     }
 }
 
 #[cfg(feature = "mpi")]
-impl Drop for MpiGuardIter {
-    fn drop(&mut self) {
-        // Ao destruir o iterador, esperamos a thread do MPI terminar o cleanup.
-        // Isso evita que o processo morra antes do MPI_Finalize ser chamado.
-        if let Some(handle) = self.thread_handle.take() {
-            // Ignoramos erros de panic na thread filha para não sujar a saída
-            let _ = handle.join();
+impl<C: Communicator + AsRaw<Raw = ffi::MPI_Comm>> Transport for MpiTransport<'_, C> {
+    fn rank(&self) -> i32 {
+        self.world.rank()
+    }
+
+    fn size(&self) -> i32 {
+        self.world.size()
+    }
+
+    fn send(&self, destination: i32, tag: Tag, data: Vec<i32>) {
+        self.reap();
+        self.pending.borrow_mut().push(PendingRequest::send(self.world, destination, tag, data));
+    }
+
+    fn flush(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.reap() > 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(CANCELLATION_POLL_INTERVAL);
         }
+        true
+    }
+
+    fn try_receive(&self, source: Option<i32>, tag: Option<Tag>) -> Option<Message> {
+        let status = match (source, tag) {
+            (Some(source), Some(tag)) => self.world.process_at_rank(source).immediate_probe_with_tag(tag.as_i32()),
+            (Some(source), None) => self.world.process_at_rank(source).immediate_probe(),
+            (None, Some(tag)) => self.world.any_process().immediate_probe_with_tag(tag.as_i32()),
+            (None, None) => self.world.any_process().immediate_probe(),
+        }?;
+        // Só esta thread usa o comunicador, então o receive pega a mensagem do probe
+        let source = status.source_rank();
+        let (data, _status) = self.world.process_at_rank(source).receive_vec_with_tag::<i32>(status.tag());
+        Some(Message { source, tag: status.tag(), data })
     }
 }
 
 #[cfg(feature = "mpi")]
-/// Calcula o denominador para o índice i no algoritmo Spigot
-#[inline]
-fn den(i: i32) -> i32 {
-    match i {
-        0 => 10,
-        _ => i.checked_mul(2).and_then(|x| x.checked_add(1)).unwrap()
-    }
+/// Envio não bloqueante (`MPI_Isend`) de um buffer de `i32`. O buffer fica emprestado ao
+/// MPI até o envio terminar. Um envio descartado pendente, que pode nunca terminar se o
+/// destino morreu, é liberado sem esperar e o seu buffer vaza.
+struct PendingRequest {
+    request: ffi::MPI_Request,
+    buffer: Vec<i32>,
+    active: bool,
 }
 
 #[cfg(feature = "mpi")]
-/// Função auxiliar para rank 0: coordena o processamento e envia para o channel
-/// Recebe o Sender (tx) criado na thread principal para enviar os dados de volta.
-fn rank0_coordinator<C: Communicator>(
-    world: &C, 
-    size: i32, 
-    n_digits: usize, 
-    tx: Sender<i32>
-) {
-    if size > 1 {
-        let last_rank = size - 1;
-        // 1. Pipeline Fill: Enviar TODOS os triggers de uma vez
-        for _digit_idx in 0..n_digits {
-            world.process_at_rank(last_rank).send_with_tag(&0i32, 0);
+impl PendingRequest {
+    /// Posta o envio do buffer inteiro para `destination`
+    fn send<C: AsRaw<Raw = ffi::MPI_Comm>>(world: &C, destination: i32, tag: Tag, buffer: Vec<i32>) -> Self {
+        // SAFETY: o buffer só é liberado depois que a requisição termina (ou nunca)
+        unsafe {
+            let mut request = ffi::RSMPI_REQUEST_NULL;
+            ffi::MPI_Isend(
+                buffer.as_ptr().cast(),
+                buffer.len() as c_int,
+                ffi::RSMPI_INT32_T,
+                destination,
+                tag.as_i32(),
+                world.as_raw(),
+                &mut request,
+            );
+            Self { request, buffer, active: true }
         }
-        
-        // 2. Finalização: Enviar flag (-1) para o último rank
-        world.process_at_rank(last_rank).send_with_tag(&(-1i32), MpiTag::Carry.as_i32());
-        
-        // 3. Coleta: Receber resultados e enviar para a main thread via channel
-        for _digit_idx in 0..n_digits {
-            let (carry_from_next, _status) = world.process_at_rank(1)
-                .receive_with_tag::<i32>(MpiTag::Carry.as_i32());
-            
-            // Se o receptor (main thread) fechou, paramos de processar
-            if tx.send(carry_from_next).is_err() {
-                break;
-            }
-        }
-    } else {
-        // Modo single-process (apenas rank 0, sem MPI real)
-        for _digit_idx in 0..n_digits {
-            if tx.send(0).is_err() {
-                break;
-            }
+    }
+
+    /// Verifica, sem bloquear, se o envio terminou
+    fn test(&mut self) -> bool {
+        // SAFETY: a requisição está ativa enquanto `active`
+        unsafe {
+            let mut done: c_int = 0;
+            ffi::MPI_Test(&mut self.request, &mut done, ffi::RSMPI_STATUS_IGNORE);
+            self.active = done == 0;
+            !self.active
         }
     }
-    // tx é dropado aqui, o que fecha o channel e sinaliza o fim para o iterador na main
 }
 
 #[cfg(feature = "mpi")]
-/// Função auxiliar para ranks 1..N: processam chunks em pipeline
-fn rank_worker<C: Communicator>(world: &C, rank: i32, size: i32, n_digits: usize) {
-    let size_usize = size as usize;
-    let rank_usize = rank as usize;
-    
-    // Calcular tamanho total do array
-    let total_len = (n_digits * 10) / 3;
-    
-    // Ranks workers são size - 1 (rank 0 não conta)
-    let num_workers = size_usize - 1;
-    
-    // Divisão de trabalho
-    let base_size = total_len / num_workers;
-    let remainder = total_len % num_workers;
-    
-    // Ajuste de índice (worker 0 é rank 1)
-    let worker_idx = rank_usize - 1; 
-    let chunk_size = base_size + if worker_idx < remainder { 1 } else { 0 };
-    let start_global_index = (worker_idx * base_size + worker_idx.min(remainder)) as i32;
-    
-    // Criar array local
-    let mut local_array = vec![2i32; chunk_size];
-    
-    // Loop de processamento
-    loop {
-        // Receber do rank "acima" (ou 0 se for o último)
-        let sender_rank = if rank == size - 1 { 0 } else { rank + 1 };
-        let (input_value, _status) = world.process_at_rank(sender_rank)
-            .receive_with_tag::<i32>(MpiTag::Carry.as_i32());
-        
-        // Verificar flag de finalização
-        if input_value == -1 {
-            // Repassar flag para baixo (exceto se for rank 1)
-            if rank > 1 {
-                let prev_rank = rank - 1;
-                world.process_at_rank(prev_rank).send_with_tag(&(-1i32), MpiTag::Carry.as_i32());
-            }
-            break;
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        if !self.active {
+            return;
+        }
+        // SAFETY: a requisição está ativa e o buffer ainda pertence a ela. Um envio liberado
+        // ainda pode ler o buffer, por isso ele nunca é desalocado.
+        unsafe {
+            ffi::MPI_Request_free(&mut self.request);
+            mem::forget(mem::take(&mut self.buffer));
         }
-        
-        // Algoritmo Spigot no chunk local
-        let carry = local_array
-            .iter_mut()
-            .enumerate()
-            .rev()
-            .fold(input_value, |carry, (local_idx, cell)| {
-                let global_idx = start_global_index + local_idx as i32;
-                
-                // Cálculos com verificação de overflow
-                let cell_x10 = (*cell as i64) * 10;
-                let global_idx_plus_1 = global_idx as i64 + 1;
-                let carry_contribution = (carry as i64) * global_idx_plus_1;
-                let current = cell_x10 + carry_contribution;
-                
-                let denominator = den(global_idx) as i64;
-                
-                *cell = (current % denominator) as i32;
-                (current / denominator) as i32
-            });
-        
-        // Enviar resultado para o rank anterior
-        let prev_rank = rank - 1;
-        world.process_at_rank(prev_rank).send_with_tag(&carry, MpiTag::Carry.as_i32());
     }
 }
 
 #[cfg(feature = "mpi")]
 /// Função Principal
-pub fn calculate_pi_mpi(n_digits: usize) -> Option<impl Iterator<Item = u8>> {
+pub fn calculate_pi_mpi(n_digits: usize) -> Option<MpiGuardIter> {
+    calculate_pi_mpi_with(n_digits, MpiOptions::default())
+}
+
+#[cfg(feature = "mpi")]
+/// Igual a `calculate_pi_mpi`, mas com as opções do pipeline configuráveis
+pub fn calculate_pi_mpi_with(n_digits: usize, mut options: MpiOptions) -> Option<MpiGuardIter> {
+    // Token desta computação: cancelado pelo token do usuário ou quando o iterador é descartado
+    let cancellation = options.cancellation.child_token();
+    options.cancellation = cancellation.clone();
+
     let (data_tx, data_rx) = channel::<i32>();
-    
+
     // Canal de Handshake: para a thread avisar qual é o rank dela
     let (rank_tx, rank_rx) = channel::<i32>();
 
+    // Nos workers, a thread avisa o fim da computação antes do MPI_Finalize
+    let (done_tx, done_rx) = channel::<Option<MpiFailure>>();
+
+    let coordinator = Coordinator::default();
+    let shared = coordinator.clone();
+    let progress = options.progress.clone();
+
     let handle = thread::spawn(move || {
         let universe = mpi::initialize().expect("Falha ao inicializar MPI");
         let world = universe.world();
-        let rank = world.rank();
-        let size = world.size();
-        
+        let transport = MpiTransport::new(&world);
+
         // 1. AVISA A MAIN THREAD QUAL É O MEU RANK
-        rank_tx.send(rank).expect("Falha ao enviar rank para main thread");
+        rank_tx.send(transport.rank()).expect("Falha ao enviar rank para main thread");
 
-        if rank == 0 {
-            rank0_coordinator(&world, size, n_digits, data_tx);
+        if transport.rank() == 0 {
+            rank0_coordinator(&transport, n_digits, &options, data_tx, &shared);
         } else {
             // Workers não usam data_tx
-            rank_worker(&world, rank, size, n_digits);
+            let _ = done_tx.send(rank_worker(&transport, n_digits, &options));
         }
     });
 
@@ -204,17 +215,134 @@ pub fn calculate_pi_mpi(n_digits: usize) -> Option<impl Iterator<Item = u8>> {
     let my_rank = rank_rx.recv().expect("Thread MPI morreu antes de reportar rank");
 
     if my_rank == 0 {
-        // Se sou Rank 0: Retorno o iterador. 
+        // Se sou Rank 0: Retorno o iterador.
         // A thread continua rodando em background e será limpa quando o iterador sair de escopo (Drop).
-        Some(MpiGuardIter {
-            inner: PiDigitsIter::new(data_rx.into_iter()),
-            thread_handle: Some(handle),
-        })
+        Some(DistributedDigits::new(n_digits, progress, data_rx, handle, cancellation, &coordinator))
     } else {
         // Se sou Worker:
         // IMPORTANTE: Precisamos esperar a thread terminar o trabalho aqui!
         // Se retornássemos None imediatamente, a função main() acabaria e mataria o processo worker.
+        // Com um rank morto o MPI_Finalize pode nunca retornar: a thread é abandonada.
+        if let Ok(Some(MpiFailure::Timeout { .. })) = done_rx.recv() {
+            return None;
+        }
         handle.join().expect("Worker thread panicou");
         None
     }
-}
\ No newline at end of file
+}
+
+#[cfg(feature = "mpi")]
+/// Calcula os dígitos de PI com MPI e os grava em `path` com MPI-IO, junto com um manifest
+/// (ver `OutputManifest`). Deve ser chamada por todos os ranks, como `calculate_pi_mpi`.
+///
+/// Só o chunk mais à esquerda produz dígitos, então só o rank 0 tem o que gravar. Ele abre
+/// o arquivo sozinho (`MPI_COMM_SELF`) e grava cada bloco de `OUTPUT_BLOCK_LEN` dígitos
+/// com `MPI_File_write_at` no seu offset assim que o bloco é corrigido, durante a
+/// computação: os dígitos não ficam guardados no rank 0 nem passam de novo pela rede. Uma
+/// escrita coletiva exigiria que cada rank produzisse os seus próprios dígitos.
+///
+/// # Retorna
+/// O manifest gravado no rank 0 e `None` nos outros ranks. No rank 0 é um erro se a
+/// computação for interrompida antes de todos os dígitos; o que foi calculado até ali
+/// fica gravado, mas sem manifest. Se um rank não respondeu (`MpiFailure::Timeout`), o MPI
+/// não é finalizado e todos os ranks que souberam do timeout retornam um erro. O processo
+/// deve terminar em seguida.
+pub fn calculate_pi_mpi_to_file(n_digits: usize, path: impl AsRef<Path>, options: MpiOptions) -> io::Result<Option<OutputManifest>> {
+    let path = path.as_ref();
+    let universe = mpi::initialize().expect("Falha ao inicializar MPI");
+    let world = universe.world();
+    let transport = MpiTransport::new(&world);
+
+    if transport.rank() != 0 {
+        if let Some(failure @ MpiFailure::Timeout { .. }) = rank_worker(&transport, n_digits, &options) {
+            mem::forget(universe);
+            return Err(io::Error::other(failure.to_string()));
+        }
+        return Ok(None);
+    }
+
+    let file = MpiFile::create(path)?;
+    let coordinator = Coordinator::default();
+    let mut output = BlockOutput::new(path, |offset, block: &[u8]| file.write_at(offset, block));
+    let written = rank0_to_file(&transport, n_digits, &options, &coordinator, &mut output, OUTPUT_BLOCK_LEN);
+    let closed = file.close();
+    let failure = *coordinator.failure.lock().unwrap();
+    if let Some(MpiFailure::Timeout { .. }) = failure {
+        mem::forget(universe);
+    }
+
+    let manifest = written.and_then(|manifest| closed.map(|()| manifest))?;
+    if manifest.digits.len() < n_digits {
+        let reason = match failure {
+            Some(failure) => failure.to_string(),
+            None => "a computação foi cancelada".to_string(),
+        };
+        return Err(io::Error::other(format!("{}: apenas {} de {} dígitos gravados", reason, manifest.digits.len(), n_digits)));
+    }
+
+    manifest.write()?;
+    Ok(Some(manifest))
+}
+
+#[cfg(feature = "mpi")]
+/// Arquivo MPI-IO aberto só por este rank, gravado com escritas independentes
+struct MpiFile(ffi::MPI_File);
+
+#[cfg(feature = "mpi")]
+impl MpiFile {
+    /// Abre `path` para escrita, criando o arquivo ou truncando um anterior
+    fn create(path: &Path) -> io::Result<Self> {
+        let c_path = path
+            .to_str()
+            .and_then(|path| CString::new(path).ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "caminho inválido para MPI-IO"))?;
+
+        // SAFETY: o caminho vive até o fim da chamada e o handle só é usado se a abertura
+        // funcionou
+        unsafe {
+            let mut handle: ffi::MPI_File = mem::zeroed();
+            let mode = (ffi::MPI_MODE_CREATE | ffi::MPI_MODE_WRONLY) as c_int;
+            let code = ffi::MPI_File_open(ffi::RSMPI_COMM_SELF, c_path.as_ptr(), mode, ffi::RSMPI_INFO_NULL, &mut handle);
+            mpi_io_result(code, "MPI_File_open")?;
+            let file = MpiFile(handle);
+            match mpi_io_result(ffi::MPI_File_set_size(file.0, 0), "MPI_File_set_size") {
+                Ok(()) => Ok(file),
+                Err(error) => {
+                    let _ = file.close();
+                    Err(error)
+                }
+            }
+        }
+    }
+
+    /// Grava `bytes` a partir de `offset`, em bytes desde o início do arquivo
+    fn write_at(&self, offset: u64, bytes: &[u8]) -> io::Result<()> {
+        // SAFETY: o buffer vive até o fim da chamada, que é bloqueante
+        let code = unsafe {
+            ffi::MPI_File_write_at(
+                self.0,
+                offset as ffi::MPI_Offset,
+                bytes.as_ptr().cast(),
+                bytes.len() as c_int,
+                ffi::RSMPI_UINT8_T,
+                ffi::RSMPI_STATUS_IGNORE,
+            )
+        };
+        mpi_io_result(code, "MPI_File_write_at")
+    }
+
+    fn close(mut self) -> io::Result<()> {
+        // SAFETY: o handle foi aberto por `create` e é fechado uma única vez
+        mpi_io_result(unsafe { ffi::MPI_File_close(&mut self.0) }, "MPI_File_close")
+    }
+}
+
+#[cfg(feature = "mpi")]
+/// Converte o código de retorno de uma chamada MPI-IO. Arquivos usam `MPI_ERRORS_RETURN`
+/// por padrão, então os erros chegam aqui em vez de abortar o job.
+fn mpi_io_result(code: c_int, call: &str) -> io::Result<()> {
+    match code as u32 == ffi::MPI_SUCCESS {
+        true => Ok(()),
+        false => Err(io::Error::other(format!("{} falhou com o código MPI {}", call, code))),
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Base do hash FNV-1a de 64 bits
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Primo do hash FNV-1a de 64 bits
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Hash FNV-1a de 64 bits. Simples e estável entre versões e plataformas, o suficiente
+/// para conferir se um arquivo de dígitos foi gravado por inteiro.
+pub fn fnv1a64(bytes: &[u8]) -> u64 {
+    fnv1a64_extend(FNV_OFFSET_BASIS, bytes)
+}
+
+/// Continua o hash FNV-1a `hash` com `bytes`, para calcular o hash de um arquivo em partes
+fn fnv1a64_extend(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME))
+}
+
+/// Descrição de um arquivo de dígitos de PI, gravada ao lado dele.
+///
+/// O arquivo contém os dígitos em ASCII, sem ponto decimal nem separadores (o dígito 0 é
+/// o `3`). O manifest fica em `<arquivo>.manifest`, com uma linha `chave: valor` por campo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputManifest {
+    /// Arquivo com os dígitos
+    pub path: PathBuf,
+    /// Índices dos dígitos gravados
+    pub digits: Range<usize>,
+    /// Hash FNV-1a de 64 bits do conteúdo do arquivo
+    pub hash: u64,
+}
+
+impl OutputManifest {
+    /// Descreve o arquivo `path` com os dígitos `digits` (em ASCII), a partir do dígito 0
+    pub fn new(path: impl AsRef<Path>, digits: &[u8]) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            digits: 0..digits.len(),
+            hash: fnv1a64(digits),
+        }
+    }
+
+    /// Caminho do manifest: o do arquivo com `.manifest` acrescentado
+    pub fn manifest_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".manifest");
+        path.into()
+    }
+
+    /// Grava o manifest em `manifest_path()`
+    pub fn write(&self) -> io::Result<()> {
+        fs::write(self.manifest_path(), self.to_string())
+    }
+}
+
+impl fmt::Display for OutputManifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "file: {}", self.path.display())?;
+        writeln!(f, "digits: {}..{}", self.digits.start, self.digits.end)?;
+        writeln!(f, "bytes: {}", self.digits.len())?;
+        writeln!(f, "encoding: ascii")?;
+        writeln!(f, "hash: fnv1a64:{:016x}", self.hash)
+    }
+}
+
+/// Arquivo de dígitos gravado em blocos consecutivos, cada um no seu offset assim que fica
+/// pronto. Acompanha o tamanho e o hash do que já foi gravado, então o manifest sai no fim
+/// sem que os dígitos fiquem guardados.
+#[cfg(any(feature = "mpi", test))]
+pub(crate) struct BlockOutput<W> {
+    path: PathBuf,
+    len: usize,
+    hash: u64,
+    /// Grava um bloco no offset dado, em bytes desde o início do arquivo
+    write_at: W,
+}
+
+#[cfg(any(feature = "mpi", test))]
+impl<W: FnMut(u64, &[u8]) -> io::Result<()>> BlockOutput<W> {
+    pub(crate) fn new(path: impl AsRef<Path>, write_at: W) -> Self {
+        Self { path: path.as_ref().to_path_buf(), len: 0, hash: FNV_OFFSET_BASIS, write_at }
+    }
+
+    /// Grava `block` logo depois dos blocos anteriores
+    pub(crate) fn append(&mut self, block: &[u8]) -> io::Result<()> {
+        (self.write_at)(self.len as u64, block)?;
+        self.len += block.len();
+        self.hash = fnv1a64_extend(self.hash, block);
+        Ok(())
+    }
+
+    /// Manifest dos dígitos gravados até aqui
+    pub(crate) fn manifest(&self) -> OutputManifest {
+        OutputManifest { path: self.path.clone(), digits: 0..self.len, hash: self.hash }
+    }
+}
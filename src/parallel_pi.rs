@@ -0,0 +1,536 @@
+use std::iter::FlatMap;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::channel;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use crate::cancellation::{CancelOnDrop, CancellationToken};
+use crate::partition::Partition;
+use crate::pi_digits_iter::PiDigitsIter;
+use crate::progress::{ProgressHook, ProgressIter};
+use crate::reciprocal::Reciprocal;
+use crate::spigot_cell::{init_cells, init_denominators, process_chunk_wavefront, CELL_BYTES};
+use crate::spsc::{self, Consumer, Producer};
+use crate::stats::{PipelineStats, StageCounters};
+
+/// Opções de execução do pipeline paralelo
+#[derive(Debug, Clone)]
+pub struct ParallelOptions {
+    /// Número de threads (estágios) do pipeline
+    pub num_threads: usize,
+    /// Capacidade (em mensagens) dos ring buffers entre os estágios
+    pub channel_bound: usize,
+    /// Número de carries enviados em cada mensagem entre estágios.
+    /// Estágios podem juntar lotes que chegaram ao mesmo tempo em uma única mensagem.
+    pub carry_batch: usize,
+    /// Número de células de cada bloco (tile) da varredura em frente de onda.
+    /// Deve caber confortavelmente no cache L1/L2.
+    pub tile_len: usize,
+    /// Número máximo de carries que um estágio processa de uma vez sobre cada tile
+    pub wavefront_depth: usize,
+    /// Fixa cada estágio em um core, na ordem do pipeline (o estágio que recebe os
+    /// triggers fica no primeiro core, o seguinte no próximo, e assim por diante).
+    ///
+    /// Independente desta opção, cada estágio aloca as suas células
+    /// e a sua tabela de recíprocos na própria thread (first-touch). Com as threads fixadas,
+    /// as páginas ficam no nó NUMA do core que as processa e as threads não migram entre
+    /// cores ou sockets.
+    ///
+    /// Requer a feature `affinity`; sem ela a opção é ignorada.
+    pub pin_threads: bool,
+    /// Intervalo, em dígitos processados, entre as tentativas de rebalanceamento entre
+    /// estágios vizinhos. Com `0` a divisão inicial do array nunca muda.
+    ///
+    /// A cada intervalo o estágio compara quanto tempo ele ficou bloqueado enviando para o
+    /// próximo estágio com quanto tempo o próximo estágio ficou parado esperando por ele,
+    /// e migra células da fronteira entre os dois na direção do estágio mais rápido.
+    pub rebalance_interval: usize,
+    /// Token para interromper a computação. Descartar o iterador também interrompe.
+    pub cancellation: CancellationToken,
+    /// Callback de progresso, com o tempo ocioso de cada estágio medido pelo tempo
+    /// bloqueado esperando o estágio anterior
+    pub progress: Option<ProgressHook>,
+}
+
+impl Default for ParallelOptions {
+    fn default() -> Self {
+        Self {
+            num_threads: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            channel_bound: 1000,
+            carry_batch: 16,
+            // 32 KiB de células por tile
+            tile_len: 32 * 1024 / CELL_BYTES,
+            wavefront_depth: 64,
+            pin_threads: false,
+            rebalance_interval: 0,
+            cancellation: CancellationToken::new(),
+            progress: None,
+        }
+    }
+}
+
+/// Calcula os dígitos de PI usando o algoritmo Spigot de forma paralela
+///
+/// # Parâmetros
+/// - `n_digits`: Número de dígitos de PI a serem calculados
+/// - `num_threads`: Número de threads para processamento paralelo
+/// - `channel_bound`: Tamanho do buffer dos canais de comunicação entre threads
+///
+/// # Retorna
+/// Um iterador sobre os dígitos de PI (u8) que não bloqueia a thread principal
+pub fn calculate_pi_parallel(n_digits: usize, num_threads: usize, channel_bound: usize) -> ParallelDigits {
+    calculate_pi_parallel_with(n_digits, ParallelOptions {
+        num_threads,
+        channel_bound,
+        ..ParallelOptions::default()
+    })
+}
+
+/// Calcula os dígitos de PI usando o algoritmo Spigot de forma paralela, com todas as
+/// opções do pipeline configuráveis
+///
+/// Cada ligação entre dois estágios é um ring buffer SPSC (um produtor, um consumidor).
+/// Os carries trafegam entre os estágios em lotes de `carry_batch` por mensagem. Cada
+/// estágio junta os lotes que já chegaram até `wavefront_depth` carries e percorre o seu chunk
+/// em tiles de `tile_len` células, da direita para a esquerda. Dentro de cada tile todos
+/// os carries do lote são processados antes de passar para o próximo tile, então o tile
+/// continua quente no cache em vez de o chunk inteiro ser varrido uma vez por dígito.
+/// Os carries de saída são exatamente os mesmos da varredura dígito a dígito.
+///
+/// Com `rebalance_interval > 0` as fronteiras entre os chunks não são fixas: estágios
+/// vizinhos migram células entre si durante a execução, com base nos tempos de espera
+/// medidos, para que o estágio mais lento deixe de ditar a vazão do pipeline.
+///
+/// # Parâmetros
+/// - `n_digits`: Número de dígitos de PI a serem calculados
+/// - `options`: Opções do pipeline
+///
+/// # Retorna
+/// Um iterador sobre os dígitos de PI (u8) que não bloqueia a thread principal, com as
+/// estatísticas de cada estágio disponíveis em `ParallelDigits::stats`
+pub fn calculate_pi_parallel_with(n_digits: usize, mut options: ParallelOptions) -> ParallelDigits {
+    // Token desta computação: cancelado pelo token do usuário ou quando o iterador é descartado
+    let cancellation = options.cancellation.child_token();
+    options.cancellation = cancellation.clone();
+
+    let num_threads = options.num_threads;
+    let channel_bound = options.channel_bound;
+    let carry_batch = options.carry_batch.max(1);
+
+    // Cálculo exato do tamanho do array
+    let total_len = (n_digits * 10) / 3;
+
+    // Cria um ring buffer para disparar o processamento dos dígitos em lotes
+    let (trigger_tx, trigger_rx) = spsc::channel::<StageMessage>(channel_bound);
+    close_on_cancel(&cancellation, &trigger_tx);
+
+    // Thread que dispara o processamento de cada lote de dígitos
+    let trigger_cancellation = cancellation.clone();
+    thread::spawn(move || {
+        let mut remaining = n_digits;
+        while remaining > 0 && !trigger_cancellation.is_cancelled() {
+            let batch_len = remaining.min(carry_batch);
+            if trigger_tx.send(StageMessage::Carries(vec![0; batch_len])).is_err() {
+                break;
+            }
+            remaining -= batch_len;
+        }
+    });
+
+    // Canal para receber o último rx da última thread criada
+    let (last_rx_tx, last_rx_rx) = channel::<Consumer<StageMessage>>();
+
+    let metrics = (0..num_threads).map(|_| StageCounters::default()).collect::<Arc<[_]>>();
+    let progress = options.progress.clone();
+    let stage_metrics = metrics.clone();
+
+    // Thread que processa os dados e envia os dados brutos para o canal
+    let coordinator = thread::spawn(move || {
+        let metrics = &*stage_metrics;
+        let options = &options;
+
+        thread::scope(|scope| {
+            let mut input_source = trigger_rx;
+            let mut cells_to_prev = None;
+
+            let partition = Partition::balanced(total_len, num_threads);
+
+            let core_ids = match options.pin_threads {
+                true => available_cores(),
+                false => Vec::new(),
+            };
+
+            // Divide o array em chunks balanceados e processa cada chunk em uma thread separada.
+            // Os chunks são criados da direita para a esquerda, que é a ordem do pipeline.
+            for (position, (i, (start_global_index, range))) in partition.iter().enumerate().rev().enumerate() {
+                let chunk_len = range.len();
+                let core_id = match core_ids.len() {
+                    0 => None,
+                    n => Some(core_ids[position % n]),
+                };
+
+                let (tx, rx) = spsc::channel(channel_bound);
+                close_on_cancel(&options.cancellation, &tx);
+                let rx_in = input_source;
+                input_source = rx;
+
+                // Canal de volta usado quando este estágio pede células ao próximo (à esquerda)
+                let (cells_from_next, next_cells_to_prev) = match i {
+                    0 => (None, None),
+                    _ => {
+                        let (back_tx, back_rx) = spsc::channel(1);
+                        close_on_cancel(&options.cancellation, &back_tx);
+                        (Some(back_rx), Some(back_tx))
+                    }
+                };
+                let cells_to_prev = std::mem::replace(&mut cells_to_prev, next_cells_to_prev);
+
+                scope.spawn(move || {
+                    if let Some(core_id) = core_id {
+                        pin_current_thread(core_id);
+                    }
+
+                    // Alocação first-touch: as células e os recíprocos são criados pela
+                    // thread que vai processá-los
+                    let range = start_global_index..start_global_index + chunk_len;
+                    let chunk = init_cells(range.clone());
+                    let dens = init_denominators(range);
+
+                    Stage {
+                        index: i,
+                        chunk,
+                        start_global_index,
+                        dens,
+                        rx_in,
+                        tx,
+                        cells_to_prev,
+                        cells_from_next,
+                        metrics,
+                        options,
+                    }.run();
+                });
+            }
+
+            // Envia o último rx (input_source) através do canal
+            let _ = last_rx_tx.send(input_source);
+        });
+    });
+
+    // Recebe o último rx da thread
+    let final_rx = last_rx_rx.recv().unwrap();
+
+    // Cria o PiDigitsIter na thread principal usando o último rx recebido,
+    // desfazendo os lotes em uma sequência de dígitos brutos
+    let into_carries: fn(StageMessage) -> Vec<i32> = StageMessage::into_carries;
+    let digits = PiDigitsIter::new(final_rx.flat_map(into_carries));
+    let idle_metrics = metrics.clone();
+    let stage_idle = move || {
+        idle_metrics.iter().map(|m| Duration::from_nanos(m.recv_wait_ns.load(Ordering::Relaxed))).collect()
+    };
+
+    ParallelDigits {
+        inner: CancelOnDrop {
+            inner: ProgressIter::new(digits, n_digits, progress, stage_idle),
+            token: cancellation,
+        },
+        metrics,
+        coordinator: Some(coordinator),
+    }
+}
+
+/// Dígitos brutos que saem do estágio 0, desfeitos dos lotes
+type RawDigits = FlatMap<Consumer<StageMessage>, Vec<i32>, fn(StageMessage) -> Vec<i32>>;
+
+/// Iterador de dígitos retornado por `calculate_pi_parallel_with`
+pub struct ParallelDigits {
+    inner: CancelOnDrop<ProgressIter<PiDigitsIter<RawDigits>>>,
+    metrics: Arc<[StageCounters]>,
+    /// Thread que coordena os estágios, aguardada quando o iterador termina
+    coordinator: Option<JoinHandle<()>>,
+}
+
+impl ParallelDigits {
+    /// Estatísticas de cada estágio, da esquerda para a direita. Durante a execução é um
+    /// retrato parcial; depois que o iterador termina os valores são finais.
+    pub fn stats(&self) -> PipelineStats {
+        PipelineStats { stages: self.metrics.iter().map(StageCounters::snapshot).collect() }
+    }
+}
+
+impl Iterator for ParallelDigits {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        let digit = self.inner.next();
+        if digit.is_none() && let Some(coordinator) = self.coordinator.take() {
+            // Espera todos os estágios terminarem para que as estatísticas fiquem completas
+            let _ = coordinator.join();
+        }
+        digit
+    }
+}
+
+/// Cores em que as threads podem ser fixadas
+#[cfg(feature = "affinity")]
+fn available_cores() -> Vec<usize> {
+    core_affinity::get_core_ids().unwrap_or_default().into_iter().map(|core| core.id).collect()
+}
+
+#[cfg(not(feature = "affinity"))]
+fn available_cores() -> Vec<usize> {
+    Vec::new()
+}
+
+/// Fixa a thread atual no core `id`
+#[cfg(feature = "affinity")]
+fn pin_current_thread(id: usize) {
+    core_affinity::set_for_current(core_affinity::CoreId { id });
+}
+
+#[cfg(not(feature = "affinity"))]
+fn pin_current_thread(_id: usize) {}
+
+/// Fecha o canal quando a computação for cancelada, acordando os estágios bloqueados nele
+fn close_on_cancel<T: Send + 'static>(cancellation: &CancellationToken, tx: &Producer<T>) {
+    let closer = tx.closer();
+    cancellation.on_cancel(move || closer.close());
+}
+
+/// Fração mínima do intervalo que um estágio precisa ficar esperando para disparar uma migração
+const REBALANCE_THRESHOLD: f64 = 0.05;
+
+/// Fração máxima do chunk migrada de uma vez, para que a divisão convirja sem oscilar
+const MAX_MIGRATION_FRACTION: f64 = 0.125;
+
+/// Mensagens que trafegam pelas ligações do pipeline (da direita para a esquerda)
+enum StageMessage {
+    /// Lote de carries, um por dígito
+    Carries(Vec<i32>),
+    /// Células cedidas pelo estágio anterior. Passam a ser o final do chunk de quem recebe,
+    /// que calcula os recíprocos delas a partir da posição global.
+    Cells(Vec<i32>),
+    /// O estágio anterior pede até `n` células do final do chunk de quem recebe.
+    /// Elas são devolvidas pelo canal de volta.
+    RequestCells(usize),
+}
+
+impl StageMessage {
+    fn into_carries(self) -> Vec<i32> {
+        match self {
+            StageMessage::Carries(carries) => carries,
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Um estágio do pipeline, dono das células `[start_global_index, start_global_index + chunk.len())`
+struct Stage<'a> {
+    index: usize,
+    chunk: Vec<i32>,
+    start_global_index: usize,
+    /// Recíprocos dos denominadores das células de `chunk`
+    dens: Vec<Reciprocal>,
+    rx_in: Consumer<StageMessage>,
+    tx: Producer<StageMessage>,
+    /// Devolve as células pedidas pelo estágio anterior (à direita)
+    cells_to_prev: Option<Producer<Vec<i32>>>,
+    /// Recebe as células pedidas ao próximo estágio (à esquerda)
+    cells_from_next: Option<Consumer<Vec<i32>>>,
+    metrics: &'a [StageCounters],
+    options: &'a ParallelOptions,
+}
+
+impl Stage<'_> {
+    /// Laço do estágio: recebe lotes de carries, processa sobre o chunk e repassa os
+    /// carries resultantes para o próximo estágio
+    fn run(mut self) {
+        let wavefront_depth = self.options.wavefront_depth.max(1);
+        let tile_len = self.options.tile_len.max(1);
+        let rebalance_interval = match self.cells_from_next {
+            Some(_) => self.options.rebalance_interval,
+            None => 0,
+        };
+
+        let mut pending = None;
+        let mut since_rebalance = 0;
+        let mut window = RebalanceWindow::new(self.metrics, self.index);
+
+        loop {
+            if self.options.cancellation.is_cancelled() {
+                break;
+            }
+
+            let message = match pending.take() {
+                Some(message) => message,
+                None => match self.recv() {
+                    Some(message) => message,
+                    None => break,
+                },
+            };
+
+            match message {
+                StageMessage::Carries(mut carries) => {
+                    // Junta os lotes que já chegaram até completar a profundidade da frente
+                    // de onda. Mensagens de controle esperam o lote atual ser processado.
+                    while carries.len() < wavefront_depth {
+                        match self.rx_in.try_recv() {
+                            Ok(StageMessage::Carries(batch)) => carries.extend(batch),
+                            Ok(other) => {
+                                pending = Some(other);
+                                break;
+                            }
+                            Err(_) => break,
+                        }
+                    }
+
+                    let busy_since = Instant::now();
+                    process_chunk_wavefront(&mut self.chunk, &self.dens, self.start_global_index, &mut carries, tile_len);
+                    let counters = &self.metrics[self.index];
+                    StageCounters::add(&counters.busy_ns, busy_since.elapsed());
+                    counters.carries.fetch_add(carries.len() as u64, Ordering::Relaxed);
+                    since_rebalance += carries.len();
+
+                    // O lote processado segue adiante como uma única mensagem
+                    if !self.send(StageMessage::Carries(carries)) {
+                        break;
+                    }
+
+                    if rebalance_interval > 0 && since_rebalance >= rebalance_interval {
+                        since_rebalance = 0;
+                        if !self.rebalance(&mut window) {
+                            break;
+                        }
+                    }
+                }
+                StageMessage::Cells(mut cells) => {
+                    let end = self.start_global_index + self.chunk.len();
+                    self.dens.extend(init_denominators(end..end + cells.len()));
+                    self.chunk.append(&mut cells);
+                }
+                StageMessage::RequestCells(n) => {
+                    let kept = cells_kept_on_request(self.chunk.len(), n);
+                    let cells = self.chunk.split_off(kept);
+                    self.dens.truncate(kept);
+                    self.metrics[self.index].migrated_cells.fetch_add(cells.len() as u64, Ordering::Relaxed);
+                    let sent = self.cells_to_prev.as_ref().is_some_and(|back| back.send(cells).is_ok());
+                    if !sent {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Recebe a próxima mensagem, contabilizando o tempo de espera
+    fn recv(&self) -> Option<StageMessage> {
+        let start = Instant::now();
+        let message = self.rx_in.recv().ok();
+        StageCounters::add(&self.metrics[self.index].recv_wait_ns, start.elapsed());
+        message
+    }
+
+    /// Envia uma mensagem ao próximo estágio, contabilizando o tempo de espera.
+    /// Retorna `false` se o próximo estágio não existe mais.
+    fn send(&self, message: StageMessage) -> bool {
+        let start = Instant::now();
+        let sent = self.tx.send(message).is_ok();
+        StageCounters::add(&self.metrics[self.index].send_wait_ns, start.elapsed());
+        sent
+    }
+
+    /// Compara os tempos de espera da última janela e migra células da fronteira com o
+    /// próximo estágio (à esquerda). Retorna `false` se algum dos vizinhos deixou de existir.
+    fn rebalance(&mut self, window: &mut RebalanceWindow) -> bool {
+        let (next_is_slow, self_is_slow) = window.advance(self.metrics, self.index);
+
+        if next_is_slow > REBALANCE_THRESHOLD && next_is_slow >= self_is_slow {
+            // O próximo estágio é o gargalo: pede células do final do chunk dele. Elas estão
+            // no mesmo dígito que as nossas, já que o pedido chega depois de todos os carries
+            // que enviamos até aqui.
+            let wanted = migration_len(self.chunk.len(), next_is_slow);
+            if wanted == 0 {
+                return true;
+            }
+            if !self.send(StageMessage::RequestCells(wanted)) {
+                return false;
+            }
+            let Some(cells) = self.cells_from_next.as_ref().and_then(|back| back.recv().ok()) else {
+                return false;
+            };
+
+            self.start_global_index -= cells.len();
+            let range = self.start_global_index..self.start_global_index + cells.len();
+            self.dens.splice(0..0, init_denominators(range));
+            self.chunk.splice(0..0, cells);
+        } else if self_is_slow > REBALANCE_THRESHOLD {
+            // Este estágio é o gargalo: cede as células do início do chunk. Os carries dos
+            // dígitos seguintes passam a sair da nova primeira célula.
+            // Sempre fica com pelo menos uma célula (um chunk vazio não cede nada)
+            let given = migration_len(self.chunk.len(), self_is_slow).min(self.chunk.len().saturating_sub(1));
+            if given == 0 {
+                return true;
+            }
+            let rest = self.chunk.split_off(given);
+            let cells = std::mem::replace(&mut self.chunk, rest);
+            self.dens.drain(..given);
+
+            self.start_global_index += given;
+            self.metrics[self.index].migrated_cells.fetch_add(given as u64, Ordering::Relaxed);
+            if !self.send(StageMessage::Cells(cells)) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Quantas células migrar de um chunk com `chunk_len` células, dada a fração do tempo em
+/// que um dos estágios foi o gargalo
+pub(crate) fn migration_len(chunk_len: usize, wait_fraction: f64) -> usize {
+    (chunk_len as f64 * (wait_fraction / 2.0).min(MAX_MIGRATION_FRACTION)) as usize
+}
+
+/// Quantas células um chunk com `chunk_len` células mantém quando o estágio anterior pede
+/// `requested` delas. Sempre fica com pelo menos uma célula, se tiver alguma.
+pub(crate) fn cells_kept_on_request(chunk_len: usize, requested: usize) -> usize {
+    chunk_len.saturating_sub(requested).max(1).min(chunk_len)
+}
+
+/// Janela de medição dos tempos de espera entre duas tentativas de rebalanceamento
+struct RebalanceWindow {
+    started: Instant,
+    self_recv_wait: u64,
+    self_send_wait: u64,
+    next_recv_wait: u64,
+}
+
+impl RebalanceWindow {
+    fn new(metrics: &[StageCounters], index: usize) -> Self {
+        let mut window = Self { started: Instant::now(), self_recv_wait: 0, self_send_wait: 0, next_recv_wait: 0 };
+        window.advance(metrics, index);
+        window
+    }
+
+    /// Fecha a janela atual e retorna as frações do tempo em que
+    /// (o próximo estágio foi o gargalo, este estágio foi o gargalo)
+    fn advance(&mut self, metrics: &[StageCounters], index: usize) -> (f64, f64) {
+        let elapsed = self.started.elapsed().as_nanos().max(1) as f64;
+        let self_recv_wait = metrics[index].recv_wait_ns.load(Ordering::Relaxed);
+        let self_send_wait = metrics[index].send_wait_ns.load(Ordering::Relaxed);
+        let next_recv_wait = match index {
+            0 => 0,
+            _ => metrics[index - 1].recv_wait_ns.load(Ordering::Relaxed),
+        };
+
+        // Bloqueado enviando: o próximo estágio não dá conta do que produzimos.
+        // O próximo estágio esperando por nós mais do que nós esperamos pelo anterior:
+        // somos nós o gargalo.
+        let next_is_slow = (self_send_wait - self.self_send_wait) as f64 / elapsed;
+        let self_is_slow = (next_recv_wait - self.next_recv_wait) as f64 / elapsed
+            - (self_recv_wait - self.self_recv_wait) as f64 / elapsed;
+
+        *self = Self { started: Instant::now(), self_recv_wait, self_send_wait, next_recv_wait };
+        (next_is_slow, self_is_slow)
+    }
+}
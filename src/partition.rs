@@ -0,0 +1,183 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Descreve como um array de `len` elementos é dividido em partes contíguas.
+///
+/// É a mesma divisão usada pelo `BalancedChunksMut`, mas sem precisar do slice: serve
+/// para quem só precisa dos índices, como cada estágio do pipeline paralelo (que aloca as
+/// próprias células) ou cada rank MPI (que só guarda a sua parte do array).
+///
+/// Cada parte é descrita por `(global_start, range)`: `range` são as posições da parte
+/// dentro do array dividido e `global_start` a posição global do primeiro elemento, que
+/// difere de `range.start` quando o array dividido começa no índice global `offset`
+/// (por exemplo, o chunk local de um rank sendo dividido entre threads).
+#[derive(Debug, Clone)]
+pub struct Partition {
+    len: usize,
+    num_parts: usize,
+    offset: usize,
+    layout: Layout,
+}
+
+#[derive(Debug, Clone)]
+enum Layout {
+    /// As primeiras `remainder` partes têm `base_size + 1` elementos e as demais `base_size`
+    Balanced { base_size: usize, remainder: usize },
+    /// Posição final de cada parte (exclusiva), calculada por uma divisão ponderada
+    Explicit(Arc<[usize]>),
+}
+
+impl Partition {
+    /// Divide `len` elementos em `num_parts` partes cujos tamanhos diferem no máximo em 1.
+    /// Os elementos que sobram da divisão vão para as primeiras partes.
+    pub fn balanced(len: usize, num_parts: usize) -> Self {
+        // Com num_parts == 0 o layout não importa: não existe nenhuma parte
+        let layout = Layout::Balanced {
+            base_size: len.checked_div(num_parts).unwrap_or(0),
+            remainder: len.checked_rem(num_parts).unwrap_or(0),
+        };
+        Self { len, num_parts, offset: 0, layout }
+    }
+
+    /// Divide `len` elementos em `weights.len()` partes com tamanhos proporcionais aos pesos
+    /// (por exemplo, a velocidade relativa de quem vai processar cada parte).
+    ///
+    /// Cada fronteira fica no elemento mais próximo da fronteira ideal, então o tamanho de
+    /// cada parte difere do proporcional em menos de 1 elemento.
+    ///
+    /// # Panics
+    /// Causa panic se algum peso for negativo ou não finito. Se todos os pesos forem zero,
+    /// eles são tratados como iguais.
+    pub fn weighted(len: usize, weights: &[f64]) -> Self {
+        Self::weighted_cost(len, weights, |_| 1.0)
+    }
+
+    /// Divide `len` elementos em `num_parts` partes contíguas de custo total aproximadamente
+    /// igual, onde `cost(i)` é o custo de processar o elemento `i`.
+    ///
+    /// # Panics
+    /// Causa panic se algum custo for negativo ou não finito. Se todos os custos forem zero,
+    /// os elementos são tratados como tendo o mesmo custo.
+    pub fn with_cost(len: usize, num_parts: usize, cost: impl Fn(usize) -> f64) -> Self {
+        Self::weighted_cost(len, &vec![1.0; num_parts], cost)
+    }
+
+    /// Combinação de `weighted` e `with_cost`: o custo total de cada parte fica proporcional
+    /// ao seu peso.
+    ///
+    /// # Panics
+    /// Causa panic se algum peso ou custo for negativo ou não finito.
+    pub fn weighted_cost(len: usize, weights: &[f64], cost: impl Fn(usize) -> f64) -> Self {
+        let ends = weighted_ends(len, weights, cost);
+        Self { len, num_parts: weights.len(), offset: 0, layout: Layout::Explicit(ends.into()) }
+    }
+
+    /// Define o índice global do primeiro elemento do array dividido
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Número total de elementos
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Retorna `true` se não existe nenhum elemento para dividir
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Número de partes
+    pub fn num_parts(&self) -> usize {
+        self.num_parts
+    }
+
+    /// Posições da parte `i` dentro do array dividido
+    pub fn range(&self, i: usize) -> Range<usize> {
+        assert!(i < self.num_parts, "Parte {} fora do intervalo (num_parts = {})", i, self.num_parts);
+        match &self.layout {
+            Layout::Balanced { base_size, remainder } => {
+                let start = base_size * i + i.min(*remainder);
+                start..start + base_size + if i < *remainder { 1 } else { 0 }
+            }
+            Layout::Explicit(ends) => {
+                let start = if i == 0 { 0 } else { ends[i - 1] };
+                start..ends[i]
+            }
+        }
+    }
+
+    /// Posição global do primeiro elemento da parte `i`
+    pub fn global_start(&self, i: usize) -> usize {
+        self.offset + self.range(i).start
+    }
+
+    /// Retorna `(global_start, range)` da parte `i`
+    pub fn part(&self, i: usize) -> (usize, Range<usize>) {
+        let range = self.range(i);
+        (self.offset + range.start, range)
+    }
+
+    /// Itera sobre `(global_start, range)` de todas as partes, da esquerda para a direita
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (usize, Range<usize>)> + ExactSizeIterator + '_ {
+        (0..self.num_parts).map(|i| self.part(i))
+    }
+}
+
+/// Calcula a posição final de cada parte para que o custo acumulado de cada uma seja
+/// proporcional ao seu peso.
+///
+/// Percorre os elementos uma vez acumulando o custo e coloca cada fronteira no ponto mais
+/// próximo do alvo: um elemento entra na parte atual se o seu ponto médio fica antes do alvo.
+fn weighted_ends(len: usize, weights: &[f64], cost: impl Fn(usize) -> f64) -> Vec<usize> {
+    assert!(
+        weights.iter().all(|w| w.is_finite() && *w >= 0.0),
+        "Pesos devem ser finitos e não negativos"
+    );
+    if weights.is_empty() {
+        return Vec::new();
+    }
+
+    let checked_cost = |i: usize| {
+        let c = cost(i);
+        assert!(c.is_finite() && c >= 0.0, "Custo do elemento {} deve ser finito e não negativo", i);
+        c
+    };
+
+    let total_weight: f64 = weights.iter().sum();
+    let (weights, total_weight) = match total_weight > 0.0 {
+        true => (weights.to_vec(), total_weight),
+        false => (vec![1.0; weights.len()], weights.len() as f64),
+    };
+
+    let total_cost: f64 = (0..len).map(checked_cost).sum();
+    let uniform = total_cost <= 0.0;
+    let element_cost = |i: usize| if uniform { 1.0 } else { checked_cost(i) };
+    let total_cost = if uniform { len as f64 } else { total_cost };
+
+    let mut ends = Vec::with_capacity(weights.len());
+    let mut accumulated_weight = 0.0;
+    let mut accumulated_cost = 0.0;
+    let mut next_cost = None;
+    let mut j = 0;
+
+    for weight in &weights[..weights.len() - 1] {
+        accumulated_weight += weight;
+        let target = total_cost * accumulated_weight / total_weight;
+
+        while j < len {
+            let c = *next_cost.get_or_insert_with(|| element_cost(j));
+            if accumulated_cost + c / 2.0 > target {
+                break;
+            }
+            accumulated_cost += c;
+            next_cost = None;
+            j += 1;
+        }
+        ends.push(j);
+    }
+    ends.push(len);
+
+    ends
+}
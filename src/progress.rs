@@ -0,0 +1,200 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Fração do novo intervalo na média móvel da vazão usada no ETA
+const THROUGHPUT_SMOOTHING: f64 = 0.3;
+
+/// Retrato do andamento de uma computação, entregue ao callback de progresso
+#[derive(Debug, Clone)]
+pub struct Progress {
+    /// Dígitos já entregues pelo iterador
+    pub digits_emitted: usize,
+    /// Total de dígitos pedidos
+    pub total_digits: usize,
+    /// Tempo desde o início da computação
+    pub elapsed: Duration,
+    /// Atualizações de célula por segundo no último intervalo: os dígitos entregues no
+    /// intervalo vezes `array_len` (cada dígito é um carry que atravessa o array inteiro,
+    /// ver `CostModel`)
+    pub cell_throughput: f64,
+    /// Tempo acumulado que cada estágio (ou rank) ficou parado esperando trabalho, da
+    /// esquerda para a direita. Vazio quando o backend não mede.
+    pub stage_idle: Vec<Duration>,
+    /// Tempo estimado até o fim, pelo modelo de custo do spigot
+    pub eta: Option<Duration>,
+}
+
+impl Progress {
+    /// Fração concluída, entre 0 e 1
+    pub fn fraction(&self) -> f64 {
+        match self.total_digits {
+            0 => 1.0,
+            total => self.digits_emitted as f64 / total as f64,
+        }
+    }
+}
+
+/// Callback de progresso, chamado no máximo uma vez a cada `interval` e uma última vez
+/// quando o iterador termina.
+///
+/// O callback roda na thread que consome o iterador, então deve ser rápido.
+#[derive(Clone)]
+pub struct ProgressHook {
+    callback: Arc<dyn Fn(&Progress) + Send + Sync>,
+    interval: Duration,
+}
+
+impl ProgressHook {
+    pub fn new(interval: Duration, callback: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        Self { callback: Arc::new(callback), interval }
+    }
+}
+
+impl fmt::Debug for ProgressHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgressHook").field("interval", &self.interval).finish_non_exhaustive()
+    }
+}
+
+/// Modelo de custo do spigot, linear nos dígitos já entregues.
+///
+/// Cada dígito percorre o array inteiro de `10n/3` células, então todo dígito custa a mesma
+/// constante `array_len` atualizações de célula. O custo da computação inteira,
+/// `n * 10n/3`, cresce com o quadrado de `n`, mas para um `n` fixo o trabalho restante é
+/// proporcional aos dígitos que faltam. O tempo restante é esse trabalho dividido pela
+/// vazão, que é medida pelos dígitos entregues: o mesmo que dígitos restantes sobre dígitos
+/// por segundo.
+#[derive(Debug, Clone, Copy)]
+pub struct CostModel {
+    n_digits: usize,
+}
+
+impl CostModel {
+    pub fn new(n_digits: usize) -> Self {
+        Self { n_digits }
+    }
+
+    /// Número de células do array
+    pub fn array_len(&self) -> usize {
+        self.n_digits * 10 / 3
+    }
+
+    /// Atualizações de célula necessárias para produzir os primeiros `digits` dígitos
+    pub fn work(&self, digits: usize) -> f64 {
+        digits as f64 * self.array_len() as f64
+    }
+
+    /// Atualizações de célula da computação inteira
+    pub fn total_work(&self) -> f64 {
+        self.work(self.n_digits)
+    }
+}
+
+/// Iterador que conta os dígitos entregues e chama o callback de progresso (sem callback,
+/// apenas repassa os dígitos)
+pub(crate) struct ProgressIter<I> {
+    inner: I,
+    hook: Option<ProgressHook>,
+    model: CostModel,
+    /// Lê o tempo ocioso acumulado de cada estágio
+    stage_idle: Box<dyn Fn() -> Vec<Duration> + Send>,
+    start: Instant,
+    last_report: Instant,
+    digits_emitted: usize,
+    digits_at_last_report: usize,
+    /// Média móvel da vazão em células por segundo
+    throughput: Option<f64>,
+    finished: bool,
+}
+
+impl<I: Iterator<Item = u8>> ProgressIter<I> {
+    pub(crate) fn new(
+        inner: I,
+        n_digits: usize,
+        hook: Option<ProgressHook>,
+        stage_idle: impl Fn() -> Vec<Duration> + Send + 'static,
+    ) -> Self {
+        let now = Instant::now();
+        Self {
+            inner,
+            hook,
+            model: CostModel::new(n_digits),
+            stage_idle: Box::new(stage_idle),
+            start: now,
+            last_report: now,
+            digits_emitted: 0,
+            digits_at_last_report: 0,
+            throughput: None,
+            finished: false,
+        }
+    }
+
+    fn report(&mut self, now: Instant) {
+        let Some(hook) = &self.hook else { return };
+        let interval = now.duration_since(self.last_report).as_secs_f64();
+        let new_work = self.model.work(self.digits_emitted - self.digits_at_last_report);
+        let current = if interval > 0.0 { new_work / interval } else { 0.0 };
+        let throughput = match self.throughput {
+            Some(previous) => previous + THROUGHPUT_SMOOTHING * (current - previous),
+            None => current,
+        };
+        self.throughput = Some(throughput);
+
+        let remaining_work = self.model.total_work() - self.model.work(self.digits_emitted);
+        let eta = match (self.finished, throughput > 0.0) {
+            (true, _) => Some(Duration::ZERO),
+            (false, true) => Some(Duration::from_secs_f64(remaining_work / throughput)),
+            (false, false) => None,
+        };
+
+        (hook.callback)(&Progress {
+            digits_emitted: self.digits_emitted,
+            total_digits: self.model.n_digits,
+            elapsed: now.duration_since(self.start),
+            cell_throughput: current,
+            stage_idle: (self.stage_idle)(),
+            eta,
+        });
+
+        self.last_report = now;
+        self.digits_at_last_report = self.digits_emitted;
+    }
+}
+
+impl<I: Iterator<Item = u8>> Iterator for ProgressIter<I> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        let Some(interval) = self.hook.as_ref().map(|hook| hook.interval) else {
+            return self.inner.next();
+        };
+
+        match self.inner.next() {
+            Some(digit) => {
+                self.digits_emitted += 1;
+                let now = Instant::now();
+                if now.duration_since(self.last_report) >= interval {
+                    self.report(now);
+                }
+                Some(digit)
+            }
+            None => {
+                if !self.finished {
+                    self.finished = true;
+                    self.report(Instant::now());
+                }
+                None
+            }
+        }
+    }
+}
+
+/// Adiciona o callback de progresso a um iterador de dígitos qualquer (por exemplo o de
+/// `calculate_pi_sequential`), sem medidas de tempo ocioso por estágio.
+pub fn with_progress<I>(digits: I, n_digits: usize, hook: ProgressHook) -> impl Iterator<Item = u8>
+where
+    I: Iterator<Item = u8>,
+{
+    ProgressIter::new(digits, n_digits, Some(hook), Vec::new)
+}
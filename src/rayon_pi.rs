@@ -0,0 +1,48 @@
+use std::sync::Arc;
+use rayon::ThreadPool;
+use crate::task_pipeline::{calculate_pi_with_spawner, Spawner, TaskOptions};
+
+/// Agenda as tarefas no pool global do rayon ou em um pool específico
+enum RayonSpawner {
+    Global,
+    Pool(Arc<ThreadPool>),
+}
+
+impl Spawner for RayonSpawner {
+    fn spawn(&self, task: Box<dyn FnOnce() + Send + 'static>) {
+        match self {
+            RayonSpawner::Global => rayon::spawn(task),
+            RayonSpawner::Pool(pool) => pool.spawn(task),
+        }
+    }
+}
+
+/// Calcula os dígitos de PI com os estágios do pipeline rodando como tarefas no pool
+/// global do rayon, com um estágio por thread do pool
+///
+/// # Parâmetros
+/// - `n_digits`: Número de dígitos de PI a serem calculados
+///
+/// # Retorna
+/// Um iterador sobre os dígitos de PI (u8)
+pub fn calculate_pi_rayon(n_digits: usize) -> impl Iterator<Item = u8> {
+    calculate_pi_rayon_with(n_digits, None, TaskOptions {
+        num_stages: rayon::current_num_threads(),
+        ..TaskOptions::default()
+    })
+}
+
+/// Igual a `calculate_pi_rayon`, mas permite escolher o pool e as opções do pipeline.
+///
+/// Com `pool == None` as tarefas rodam no pool global do rayon, compartilhado com o
+/// resto da aplicação. Nenhuma thread do sistema é criada pelo pipeline.
+///
+/// O iterador não deve ser consumido de dentro de uma tarefa do mesmo pool (ver
+/// `calculate_pi_with_spawner`).
+pub fn calculate_pi_rayon_with(n_digits: usize, pool: Option<Arc<ThreadPool>>, options: TaskOptions) -> impl Iterator<Item = u8> {
+    let spawner = match pool {
+        Some(pool) => RayonSpawner::Pool(pool),
+        None => RayonSpawner::Global,
+    };
+    calculate_pi_with_spawner(n_digits, spawner, options)
+}
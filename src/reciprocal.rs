@@ -0,0 +1,50 @@
+/// Recíproco pré-calculado de um divisor de 32 bits (estilo libdivide).
+///
+/// A divisão de hardware é a operação mais cara do laço interno do Spigot, e o
+/// denominador `den(i)` muda a cada célula. Como ele é fixo para cada posição do
+/// array, podemos calcular uma única vez um "número mágico" por célula e trocar
+/// cada `/` e `%` por multiplicações e deslocamentos.
+///
+/// # Algoritmo
+///
+/// Usa o método de Lemire, Kaser e Kurz ("Faster Remainder by Direct Computation"):
+/// com `M = ⌊(2^64 - 1) / d⌋ + 1`, para qualquer `n < 2^32` vale
+/// - `n / d == (M * n) >> 64`
+/// - `n % d == ((M * n mod 2^64) * d) >> 64`
+///
+/// Os dois resultados são exatos para todos os numeradores e divisores de 32 bits
+/// (com `d > 1`), então não existe caso de correção.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reciprocal {
+    magic: u64,
+    divisor: u32,
+}
+
+impl Reciprocal {
+    /// Pré-calcula o recíproco de `divisor`.
+    ///
+    /// # Panics
+    /// Causa panic se `divisor < 2` (com `d == 1` o número mágico não cabe em 64 bits).
+    pub fn new(divisor: u32) -> Self {
+        assert!(divisor > 1, "Reciprocal requer divisor > 1, recebido {}", divisor);
+        Self {
+            magic: u64::MAX / divisor as u64 + 1,
+            divisor,
+        }
+    }
+
+    /// Retorna o divisor original
+    #[inline]
+    pub fn divisor(&self) -> u32 {
+        self.divisor
+    }
+
+    /// Calcula `(n / d, n % d)` sem usar instruções de divisão.
+    #[inline]
+    pub fn div_rem(&self, n: u32) -> (u32, u32) {
+        let low_bits = self.magic.wrapping_mul(n as u64);
+        let quotient = ((self.magic as u128 * n as u128) >> 64) as u32;
+        let remainder = ((low_bits as u128 * self.divisor as u128) >> 64) as u32;
+        (quotient, remainder)
+    }
+}
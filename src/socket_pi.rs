@@ -0,0 +1,610 @@
+use std::fmt;
+#[cfg(unix)]
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use crate::distributed::{
+    rank0_coordinator, rank_worker, Coordinator, DistributedDigits, DistributedOptions, RankFailure, CANCELLATION_POLL_INTERVAL,
+};
+use crate::transport::{Mailbox, Message, Tag, Transport};
+
+/// Endereço em que um rank escuta: `host:porta` para TCP ou `unix:<caminho>` para um
+/// socket Unix
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SocketAddress {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl SocketAddress {
+    /// Troca um IP não especificado (`0.0.0.0` ou `::`) pelo IP de onde a conexão veio,
+    /// para que os outros ranks consigam se conectar a um worker que escuta em todas as
+    /// interfaces
+    fn reachable_from(self, peer: Option<IpAddr>) -> Self {
+        match (&self, peer) {
+            (SocketAddress::Tcp(address), Some(peer)) => match address.parse::<SocketAddr>() {
+                Ok(address) if address.ip().is_unspecified() => SocketAddress::Tcp(SocketAddr::new(peer, address.port()).to_string()),
+                _ => self,
+            },
+            _ => self,
+        }
+    }
+}
+
+impl FromStr for SocketAddress {
+    type Err = io::Error;
+
+    fn from_str(address: &str) -> io::Result<Self> {
+        match address.strip_prefix("unix:") {
+            #[cfg(unix)]
+            Some(path) => Ok(SocketAddress::Unix(PathBuf::from(path))),
+            #[cfg(not(unix))]
+            Some(_) => Err(io::Error::new(io::ErrorKind::Unsupported, "sockets Unix não são suportados nesta plataforma")),
+            None => Ok(SocketAddress::Tcp(address.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for SocketAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SocketAddress::Tcp(address) => write!(f, "{}", address),
+            #[cfg(unix)]
+            SocketAddress::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Conexão TCP ou Unix
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    fn connect(address: &SocketAddress) -> io::Result<Self> {
+        match address {
+            SocketAddress::Tcp(address) => {
+                let stream = TcpStream::connect(address)?;
+                // Lotes pequenos não devem esperar o algoritmo de Nagle
+                stream.set_nodelay(true)?;
+                Ok(Stream::Tcp(stream))
+            }
+            #[cfg(unix)]
+            SocketAddress::Unix(path) => Ok(Stream::Unix(UnixStream::connect(path)?)),
+        }
+    }
+
+    /// Tenta conectar até `timeout`, para tolerar um rank que ainda não começou a escutar
+    fn connect_with_retry(address: &SocketAddress, timeout: Duration) -> io::Result<Self> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match Stream::connect(address) {
+                Err(_) if Instant::now() < deadline => thread::sleep(CONNECT_RETRY_INTERVAL),
+                result => return result,
+            }
+        }
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Stream::Tcp(stream) => stream.try_clone().map(Stream::Tcp),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.try_clone().map(Stream::Unix),
+        }
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.set_read_timeout(timeout),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.set_write_timeout(timeout),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_write_timeout(timeout),
+        }
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.shutdown(Shutdown::Both),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.shutdown(Shutdown::Both),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Socket que aceita conexões de outros ranks. O arquivo de um socket Unix é removido
+/// quando o listener é descartado; um arquivo que sobrou de um processo que terminou sem
+/// removê-lo é substituído no bind.
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+impl Listener {
+    fn bind(address: &SocketAddress) -> io::Result<Self> {
+        match address {
+            SocketAddress::Tcp(address) => Ok(Listener::Tcp(TcpListener::bind(address)?)),
+            #[cfg(unix)]
+            SocketAddress::Unix(path) => {
+                let listener = match UnixListener::bind(path) {
+                    Err(error) if error.kind() == io::ErrorKind::AddrInUse && is_stale_socket(path) => {
+                        fs::remove_file(path)?;
+                        UnixListener::bind(path)?
+                    }
+                    result => result?,
+                };
+                Ok(Listener::Unix(listener, path.clone()))
+            }
+        }
+    }
+
+    /// Endereço efetivo, com a porta escolhida pelo sistema quando a pedida é 0
+    fn local_address(&self) -> io::Result<SocketAddress> {
+        match self {
+            Listener::Tcp(listener) => Ok(SocketAddress::Tcp(listener.local_addr()?.to_string())),
+            #[cfg(unix)]
+            Listener::Unix(_, path) => Ok(SocketAddress::Unix(path.clone())),
+        }
+    }
+
+    /// Aceita uma conexão, esperando no máximo `timeout`. Retorna também o IP de origem
+    /// das conexões TCP.
+    fn accept(&self, timeout: Duration) -> io::Result<(Stream, Option<IpAddr>)> {
+        self.set_nonblocking(true)?;
+        let deadline = Instant::now() + timeout;
+        let accepted = loop {
+            let accepted = match self {
+                Listener::Tcp(listener) => listener.accept().map(|(stream, peer)| (Stream::Tcp(stream), Some(peer.ip()))),
+                #[cfg(unix)]
+                Listener::Unix(listener, _) => listener.accept().map(|(stream, _)| (Stream::Unix(stream), None)),
+            };
+            match accepted {
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock && Instant::now() < deadline => {
+                    thread::sleep(CONNECT_RETRY_INTERVAL);
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                    break Err(io::Error::new(io::ErrorKind::TimedOut, format!("nenhum rank se conectou em {:?}", timeout)));
+                }
+                result => break result,
+            }
+        };
+        self.set_nonblocking(false)?;
+
+        let (stream, peer) = accepted?;
+        match &stream {
+            Stream::Tcp(stream) => {
+                stream.set_nonblocking(false)?;
+                stream.set_nodelay(true)?;
+            }
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_nonblocking(false)?,
+        }
+        Ok((stream, peer))
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        match self {
+            Listener::Tcp(listener) => listener.set_nonblocking(nonblocking),
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => listener.set_nonblocking(nonblocking),
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Listener::Unix(_, path) = self {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// `true` se ninguém aceita conexões no socket Unix em `path`, ou seja, o arquivo sobrou de
+/// um processo que terminou sem removê-lo
+#[cfg(unix)]
+fn is_stale_socket(path: &Path) -> bool {
+    matches!(UnixStream::connect(path), Err(error) if error.kind() == io::ErrorKind::ConnectionRefused)
+}
+
+/// Intervalo entre tentativas de conexão e de accept durante a formação da malha
+const CONNECT_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Primeira linha de todo handshake, nos dois sentidos: quem conecta a envia e quem aceita
+/// a devolve. Uma conexão que não começa com ela (de outro programa ou de outra versão do
+/// protocolo) é recusada.
+const PROTOCOL: &str = "spigot-pi-socket 1";
+
+/// Tempo máximo para uma conexão recém-aceita se apresentar. Bem menor que
+/// `receive_timeout`, para que uma conexão estranha que não envia nada não atrase a
+/// formação da malha.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Tamanho máximo de uma linha do handshake, em bytes (um caminho de socket Unix cabe folgado)
+const MAX_LINE_LEN: u64 = 4096;
+
+/// Maior mensagem de controle, em valores: `Stats`, com quatro `u64`
+const MAX_CONTROL_LEN: usize = 8;
+
+/// Conexão já estabelecida com outro rank: a metade de escrita e o leitor, que pode ter
+/// bytes do handshake já lidos
+type Connection = (Stream, BufReader<Stream>);
+
+/// Grava uma linha de texto do handshake
+fn write_line(stream: &mut Stream, line: &str) -> io::Result<()> {
+    stream.write_all(format!("{}\n", line).as_bytes())
+}
+
+/// Lê uma linha de texto do handshake, sem o `\n`. Uma linha maior que `MAX_LINE_LEN` é
+/// um erro, para que um par com defeito não faça o rank acumular dados sem limite.
+fn read_line(reader: &mut BufReader<Stream>) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.by_ref().take(MAX_LINE_LEN).read_line(&mut line)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "conexão fechada durante o handshake"));
+    }
+    if !line.ends_with('\n') {
+        return Err(invalid_data(format!("linha do handshake maior que {} bytes ou incompleta", MAX_LINE_LEN)));
+    }
+    Ok(line.trim_end().to_string())
+}
+
+/// Confere que o outro lado fala o mesmo protocolo (ver `PROTOCOL`)
+fn expect_protocol(reader: &mut BufReader<Stream>) -> io::Result<()> {
+    match read_line(reader)? {
+        line if line == PROTOCOL => Ok(()),
+        line => Err(invalid_data(format!("protocolo desconhecido no handshake: {:?}", line))),
+    }
+}
+
+/// Maior mensagem que pode chegar de outro rank, em valores: um lote de carries (o rank 0
+/// nunca envia mais que `carry_batch` de uma vez e os workers repassam os lotes como
+/// chegaram) ou uma mensagem de controle
+fn max_frame_len(carry_batch: usize) -> usize {
+    carry_batch.max(MAX_CONTROL_LEN)
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Grava uma mensagem: tag e número de valores (`u32`), seguidos dos valores, tudo em
+/// little-endian
+fn write_frame(stream: &mut Stream, tag: i32, data: &[i32]) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(8 + data.len() * 4);
+    frame.extend_from_slice(&tag.to_le_bytes());
+    frame.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    data.iter().for_each(|value| frame.extend_from_slice(&value.to_le_bytes()));
+    stream.write_all(&frame)
+}
+
+/// Lê uma mensagem gravada por `write_frame`. Uma mensagem com mais de `max_len` valores
+/// é um erro, em vez de uma alocação do tamanho que veio da rede.
+fn read_frame(reader: &mut BufReader<Stream>, max_len: usize) -> io::Result<(i32, Vec<i32>)> {
+    let mut header = [0u8; 8];
+    reader.read_exact(&mut header)?;
+    let tag = i32::from_le_bytes(header[..4].try_into().unwrap());
+    let len = u32::from_le_bytes(header[4..].try_into().unwrap()) as usize;
+    if len > max_len {
+        return Err(invalid_data(format!("mensagem com {} valores, o máximo é {}", len, max_len)));
+    }
+    let mut bytes = vec![0u8; len * 4];
+    reader.read_exact(&mut bytes)?;
+    let data = bytes.chunks_exact(4).map(|value| i32::from_le_bytes(value.try_into().unwrap())).collect();
+    Ok((tag, data))
+}
+
+/// Ligação com outro rank: a fila da thread que escreve e uma cópia do socket para
+/// encerrar a conexão
+struct Peer {
+    queue: Sender<(i32, Vec<i32>)>,
+    writer: JoinHandle<()>,
+    stream: Stream,
+}
+
+/// `Transport` sobre uma malha completa de conexões TCP ou Unix.
+///
+/// Cada conexão tem uma thread que lê as mensagens para a caixa de entrada do rank e uma
+/// que escreve as mensagens enfileiradas por `send`, na ordem. Um envio fica pendente até
+/// ser escrito no socket; um vizinho morto deixa os envios pendentes e é detectado pelo
+/// timeout de `flush`, como no MPI.
+struct SocketTransport {
+    rank: i32,
+    mailbox: Arc<Mailbox>,
+    peers: Vec<Option<Peer>>,
+    pending: Arc<AtomicUsize>,
+}
+
+impl SocketTransport {
+    /// Liga o rank `rank` às conexões com os outros ranks (o índice é o rank do outro
+    /// lado; `None` na posição do próprio rank). Uma conexão que recebe uma mensagem com
+    /// mais de `max_frame_len` valores para de ser lida.
+    fn new(rank: i32, connections: Vec<Option<Connection>>, timeout: Duration, max_frame_len: usize) -> io::Result<Self> {
+        let mailbox = Arc::new(Mailbox::default());
+        let pending = Arc::new(AtomicUsize::new(0));
+        let mut peers = Vec::with_capacity(connections.len());
+
+        for (peer_rank, connection) in connections.into_iter().enumerate() {
+            let Some((mut stream, mut reader)) = connection else {
+                peers.push(None);
+                continue;
+            };
+            // O handshake tem timeout; depois a vivacidade é verificada pelo pipeline
+            reader.get_ref().set_read_timeout(None)?;
+            stream.set_write_timeout(Some(timeout))?;
+            let shutdown = stream.try_clone()?;
+
+            let inbox = mailbox.clone();
+            thread::spawn(move || {
+                while let Ok((tag, data)) = read_frame(&mut reader, max_frame_len) {
+                    inbox.push(Message { source: peer_rank as i32, tag, data });
+                }
+            });
+
+            let (queue, outgoing) = channel::<(i32, Vec<i32>)>();
+            let written = pending.clone();
+            let writer = thread::spawn(move || {
+                for (tag, data) in outgoing {
+                    // Um envio que falhou continua pendente: o `flush` vai estourar o timeout
+                    if write_frame(&mut stream, tag, &data).is_err() {
+                        break;
+                    }
+                    written.fetch_sub(1, Ordering::AcqRel);
+                }
+            });
+            peers.push(Some(Peer { queue, writer, stream: shutdown }));
+        }
+
+        Ok(Self { rank, mailbox, peers, pending })
+    }
+}
+
+impl Transport for SocketTransport {
+    fn rank(&self) -> i32 {
+        self.rank
+    }
+
+    fn size(&self) -> i32 {
+        self.peers.len() as i32
+    }
+
+    fn send(&self, destination: i32, tag: Tag, data: Vec<i32>) {
+        debug_assert!(destination != self.rank, "o rank {} não envia mensagens para si mesmo", self.rank);
+        let Some(peer) = &self.peers[destination as usize] else { return };
+
+        // Só fica pendente o que entrou na fila. Se a thread de escrita já terminou, o envio
+        // que falhou nela continua pendente e o `flush` estoura o timeout do mesmo jeito.
+        self.pending.fetch_add(1, Ordering::AcqRel);
+        if peer.queue.send((tag.as_i32(), data)).is_err() {
+            self.pending.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+
+    fn flush(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.pending.load(Ordering::Acquire) > 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(CANCELLATION_POLL_INTERVAL);
+        }
+        true
+    }
+
+    fn try_receive(&self, source: Option<i32>, tag: Option<Tag>) -> Option<Message> {
+        self.mailbox.take(source, tag)
+    }
+}
+
+impl Drop for SocketTransport {
+    fn drop(&mut self) {
+        // Escreve o que ainda está na fila (uma escrita travada desiste depois do timeout)
+        // e fecha as conexões, o que também encerra as threads de leitura dos dois lados
+        for Peer { queue, writer, stream } in self.peers.drain(..).flatten() {
+            drop(queue);
+            let _ = writer.join();
+            let _ = stream.shutdown();
+        }
+    }
+}
+
+/// Rank 0 do backend com sockets: escuta no seu endereço, espera os workers se juntarem e
+/// coordena a computação, como o rank 0 de `calculate_pi_mpi`.
+///
+/// # Exemplo
+/// ```no_run
+/// use spigot_pi::{DistributedOptions, SocketCoordinator};
+///
+/// // Cada worker chama `run_socket_worker` com o endereço "coordenador:7000"
+/// let coordinator = SocketCoordinator::bind(&"0.0.0.0:7000".parse()?)?;
+/// for digit in coordinator.run(1000, 2, DistributedOptions::default())? {
+///     print!("{}", digit);
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct SocketCoordinator {
+    listener: Listener,
+}
+
+impl SocketCoordinator {
+    /// Escuta em `address`. Com a porta 0, o sistema escolhe uma (ver `local_address`).
+    pub fn bind(address: &SocketAddress) -> io::Result<Self> {
+        Ok(Self { listener: Listener::bind(address)? })
+    }
+
+    /// Endereço em que o coordenador escuta, a ser passado aos workers
+    pub fn local_address(&self) -> io::Result<SocketAddress> {
+        self.listener.local_address()
+    }
+
+    /// Espera `workers` workers se conectarem (no máximo `receive_timeout` por worker),
+    /// atribui os ranks na ordem de chegada e calcula `n_digits` dígitos.
+    ///
+    /// `n_digits`, `carry_batch` e `receive_timeout` são enviados aos workers; cada worker
+    /// usa os seus próprios `num_threads` e `tile_len`. Conexões que não seguem o handshake
+    /// são descartadas. Retorna um erro se a malha de conexões não se formar; falhas durante
+    /// a computação aparecem em `DistributedDigits::failure`.
+    pub fn run(self, n_digits: usize, workers: usize, mut options: DistributedOptions) -> io::Result<DistributedDigits> {
+        let size = workers + 1;
+        let timeout = options.receive_timeout;
+        let carry_batch = options.carry_batch.max(1);
+
+        // Cada worker se apresenta com o endereço em que escuta os outros workers
+        let mut joined = Vec::with_capacity(workers);
+        while joined.len() < workers {
+            let (stream, peer) = self.listener.accept(timeout)?;
+            stream.set_read_timeout(Some(timeout.min(HANDSHAKE_TIMEOUT)))?;
+            let mut reader = BufReader::new(stream.try_clone()?);
+            let address = expect_protocol(&mut reader).and_then(|()| read_line(&mut reader)?.parse::<SocketAddress>());
+            if let Ok(address) = address {
+                joined.push((stream, reader, address.reachable_from(peer)));
+            }
+        }
+
+        // Atribuição: `rank size n_digits carry_batch timeout_ms`, seguida dos endereços dos
+        // ranks 1..size
+        let addresses = joined.iter().map(|(_, _, address)| address.to_string()).collect::<Vec<_>>();
+        let mut connections = vec![None];
+        for (i, (mut stream, reader, _)) in joined.into_iter().enumerate() {
+            write_line(&mut stream, PROTOCOL)?;
+            write_line(&mut stream, &format!("{} {} {} {} {}", i + 1, size, n_digits, carry_batch, timeout.as_millis()))?;
+            for address in &addresses {
+                write_line(&mut stream, address)?;
+            }
+            connections.push(Some((stream, reader)));
+        }
+        let transport = SocketTransport::new(0, connections, timeout, max_frame_len(carry_batch))?;
+
+        // Token desta computação: cancelado pelo token do usuário ou quando o iterador é descartado
+        let cancellation = options.cancellation.child_token();
+        options.cancellation = cancellation.clone();
+
+        let (tx, rx) = channel::<i32>();
+        let coordinator = Coordinator::default();
+        let shared = coordinator.clone();
+        let progress = options.progress.clone();
+        let handle = thread::spawn(move || rank0_coordinator(&transport, n_digits, &options, tx, &shared));
+
+        Ok(DistributedDigits::new(n_digits, progress, rx, handle, cancellation, &coordinator))
+    }
+}
+
+/// Executa um worker do backend com sockets: escuta em `listen`, junta-se ao coordenador
+/// em `coordinator`, conecta-se aos outros workers e processa o seu chunk até o fim da
+/// computação.
+///
+/// `listen` pode usar a porta 0 e, em TCP, o IP `0.0.0.0`: os outros ranks recebem o IP
+/// visto pelo coordenador. O coordenador pode começar depois do worker; a conexão é
+/// tentada até `options.receive_timeout`, que depois é substituído pelo do coordenador,
+/// assim como `carry_batch`.
+///
+/// # Retorna
+/// O motivo do `Abort`, se a computação foi abortada por uma falha, ou um erro se a malha
+/// de conexões não se formar.
+pub fn run_socket_worker(
+    coordinator: &SocketAddress,
+    listen: &SocketAddress,
+    mut options: DistributedOptions,
+) -> io::Result<Option<RankFailure>> {
+    let listener = Listener::bind(listen)?;
+    let timeout = options.receive_timeout;
+    let mut stream = Stream::connect_with_retry(coordinator, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    write_line(&mut stream, PROTOCOL)?;
+    write_line(&mut stream, &listener.local_address()?.to_string())?;
+
+    expect_protocol(&mut reader)?;
+    let assignment = read_line(&mut reader)?;
+    let values = assignment.split(' ').map(|value| value.parse::<u64>().ok()).collect::<Option<Vec<_>>>();
+    let (rank, size, n_digits, carry_batch, timeout_ms) = match values.as_deref() {
+        Some(&[rank, size, n_digits, carry_batch, timeout_ms]) if rank >= 1 && rank < size && carry_batch >= 1 => {
+            (rank as usize, size as usize, n_digits as usize, carry_batch as usize, timeout_ms)
+        }
+        _ => return Err(invalid_data(format!("atribuição inválida do coordenador: {:?}", assignment))),
+    };
+    options.carry_batch = carry_batch;
+    options.receive_timeout = Duration::from_millis(timeout_ms);
+    let addresses = (1..size).map(|_| read_line(&mut reader)?.parse::<SocketAddress>()).collect::<io::Result<Vec<_>>>()?;
+
+    let mut connections = (0..size).map(|_| None).collect::<Vec<Option<Connection>>>();
+    connections[0] = Some((stream, reader));
+
+    // Cada worker se conecta aos de rank menor e aceita os de rank maior, que se
+    // apresentam com o próprio rank. Quem aceita responde com o protocolo.
+    for (peer, address) in (1..rank).zip(&addresses) {
+        let mut stream = Stream::connect_with_retry(address, options.receive_timeout)?;
+        stream.set_read_timeout(Some(options.receive_timeout))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        write_line(&mut stream, PROTOCOL)?;
+        write_line(&mut stream, &rank.to_string())?;
+        expect_protocol(&mut reader)?;
+        connections[peer] = Some((stream, reader));
+    }
+    let mut accepted = rank + 1;
+    while accepted < size {
+        let (mut stream, _) = listener.accept(options.receive_timeout)?;
+        stream.set_read_timeout(Some(options.receive_timeout.min(HANDSHAKE_TIMEOUT)))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        // Conexões que não seguem o handshake são descartadas
+        let Ok(line) = expect_protocol(&mut reader).and_then(|()| read_line(&mut reader)) else { continue };
+        let peer = line.parse::<usize>().ok().filter(|&peer| peer > rank && peer < size && connections[peer].is_none());
+        let Some(peer) = peer else {
+            return Err(invalid_data(format!("rank inválido no handshake: {:?}", line)));
+        };
+        write_line(&mut stream, PROTOCOL)?;
+        connections[peer] = Some((stream, reader));
+        accepted += 1;
+    }
+    drop(listener);
+
+    let transport = SocketTransport::new(rank as i32, connections, options.receive_timeout, max_frame_len(carry_batch))?;
+    Ok(rank_worker(&transport, n_digits, &options))
+}
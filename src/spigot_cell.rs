@@ -0,0 +1,124 @@
+use std::ops::Range;
+use crate::den;
+use crate::reciprocal::Reciprocal;
+
+/// Bytes lidos por célula a cada dígito: o valor (`i32`) e o recíproco do denominador.
+///
+/// Os valores ficam num array próprio, o único escrito pelo laço interno, e os recíprocos
+/// numa tabela somente leitura ao lado dele, com uma entrada por célula do chunk. Assim o
+/// array escrito a cada dígito tem 4 bytes por célula e as células que migram entre
+/// estágios (ver `ParallelOptions::rebalance_interval`) levam só o valor: quem as recebe
+/// recalcula os recíprocos, que dependem apenas da posição global.
+pub const CELL_BYTES: usize = size_of::<i32>() + size_of::<Reciprocal>();
+
+/// Aloca e inicializa (com o valor 2) as células das posições globais em `range`.
+///
+/// As páginas do vetor são tocadas pela primeira vez pela thread que chama esta função,
+/// então em máquinas NUMA elas ficam no nó de memória dessa thread.
+pub fn init_cells(range: Range<usize>) -> Vec<i32> {
+    vec![2; range.len()]
+}
+
+/// Tabela com o recíproco pré-calculado do denominador `den(i)` de cada posição global em
+/// `range`, para que o laço interno não precise de nenhuma instrução de divisão
+pub fn init_denominators(range: Range<usize>) -> Vec<Reciprocal> {
+    range
+        .map(|global_idx| {
+            let global_idx: i32 = global_idx.try_into().expect("Overflow ao converter global_idx");
+            let denominator: u32 = den(global_idx)
+                .try_into()
+                .expect("Denominador negativo");
+            Reciprocal::new(denominator)
+        })
+        .collect()
+}
+
+/// Processa um chunk de células do Spigot da direita para a esquerda.
+///
+/// # Parâmetros
+/// - `cells`: Os valores das células do chunk
+/// - `dens`: Os recíprocos dos denominadores das mesmas células (ver `init_denominators`)
+/// - `start_global_index`: Índice global da primeira célula do chunk
+/// - `carry_in`: O carry vindo do chunk à direita (ou 0 se for o último chunk)
+///
+/// # Retorna
+/// O carry que deve ser repassado para o chunk à esquerda. Para o chunk que contém o
+/// índice 0 esse valor é o dígito bruto de PI.
+#[inline]
+pub fn process_chunk(cells: &mut [i32], dens: &[Reciprocal], start_global_index: usize, carry_in: i32) -> i32 {
+    debug_assert_eq!(cells.len(), dens.len(), "Cada célula precisa do seu denominador");
+    cells
+        .iter_mut()
+        .zip(dens)
+        .enumerate()
+        .rev()
+        .fold(carry_in, |carry, (idx, (cell, den))| {
+            let global_idx: i32 = start_global_index
+                .checked_add(idx)
+                .and_then(|x: usize| x.try_into().ok())
+                .expect("Overflow ao calcular global_idx");
+
+            let cell_x10: i32 = cell
+                .checked_mul(10i32)
+                .expect("Overflow ao multiplicar cell por 10");
+
+            let global_idx_plus_one: i32 = global_idx
+                .checked_add(1i32)
+                .expect("Overflow ao adicionar 1 a global_idx");
+
+            let carry_x_idx: i32 = carry
+                .checked_mul(global_idx_plus_one)
+                .expect("Overflow ao multiplicar carry por (global_idx + 1)");
+
+            let current: u32 = cell_x10
+                .checked_add(carry_x_idx)
+                .and_then(|x| x.try_into().ok())
+                .expect("Overflow ou valor negativo ao calcular current");
+
+            // Divisão e resto pelo denominador usando o recíproco pré-calculado
+            let (div, rem) = den.div_rem(current);
+
+            *cell = rem as i32;
+            div as i32
+        })
+}
+
+/// Processa um lote de carries sobre um chunk em ordem de frente de onda (wavefront).
+///
+/// O chunk é dividido em tiles de `tile_len` células, percorridos da direita para a
+/// esquerda. Cada tile recebe todos os carries do lote (em ordem de dígito) antes de
+/// passar para o tile seguinte, então ele é lido do cache `carries.len()` vezes em vez
+/// de uma só. O resultado é idêntico a chamar `process_chunk` uma vez por carry, já que
+/// o dígito `d` de um tile só depende do estado do tile após o dígito `d - 1` e do
+/// carry que o tile à direita produziu para o dígito `d`.
+///
+/// # Parâmetros
+/// - `cells`: Os valores das células do chunk
+/// - `dens`: Os recíprocos dos denominadores das mesmas células
+/// - `start_global_index`: Índice global da primeira célula do chunk
+/// - `carries`: Os carries de entrada, em ordem de dígito. Ao final contém os carries de saída.
+/// - `tile_len`: Número de células de cada tile
+///
+/// # Panics
+/// Causa panic se `cells` e `dens` tiverem tamanhos diferentes.
+pub fn process_chunk_wavefront(
+    cells: &mut [i32],
+    dens: &[Reciprocal],
+    start_global_index: usize,
+    carries: &mut [i32],
+    tile_len: usize,
+) {
+    assert_eq!(cells.len(), dens.len(), "Cada célula precisa do seu denominador");
+    let mut tile_end = cells.len();
+
+    for tile in cells.rchunks_mut(tile_len.max(1)) {
+        let tile_start = tile_end - tile.len();
+        let tile_dens = &dens[tile_start..tile_end];
+
+        for carry in carries.iter_mut() {
+            *carry = process_chunk(tile, tile_dens, start_global_index + tile_start, *carry);
+        }
+
+        tile_end = tile_start;
+    }
+}
@@ -0,0 +1,282 @@
+use std::cell::{Cell, UnsafeCell};
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::ops::Deref;
+use std::sync::atomic::{fence, AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{RecvError, SendError, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, Thread};
+
+/// Quantas vezes um lado verifica o buffer girando antes de estacionar a thread
+const SPIN_LIMIT: u32 = 128;
+
+/// Cria um ring buffer limitado com exatamente um produtor e um consumidor.
+///
+/// Cada ligação do pipeline paralelo tem um único estágio escrevendo e um único estágio
+/// lendo, então não precisamos da sincronização geral do `mpsc`: o produtor é o único que
+/// escreve em `tail` e o consumidor o único que escreve em `head`.
+///
+/// Quando o buffer está cheio (ou vazio), o lado bloqueado primeiro gira por algumas
+/// iterações, já que o outro lado costuma responder em poucos nanossegundos, e só então
+/// estaciona a thread com `thread::park`, sendo acordado pelo outro lado.
+///
+/// A API segue a do `std::sync::mpsc`: `send` falha quando o consumidor foi descartado e
+/// `recv` falha quando o produtor foi descartado e o buffer está vazio.
+///
+/// # Parâmetros
+/// - `capacity`: Número máximo de itens no buffer (no mínimo 1)
+pub fn channel<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    let capacity = capacity.max(1);
+    let shared = Arc::new(Shared {
+        buffer: (0..capacity).map(|_| UnsafeCell::new(MaybeUninit::uninit())).collect(),
+        head: CachePadded(AtomicUsize::new(0)),
+        tail: CachePadded(AtomicUsize::new(0)),
+        closed: AtomicBool::new(false),
+        producer_waiter: Waiter::default(),
+        consumer_waiter: Waiter::default(),
+    });
+
+    (Producer { shared: shared.clone(), _not_sync: PhantomData }, Consumer { shared, _not_sync: PhantomData })
+}
+
+/// Alinha o valor em sua própria linha de cache para evitar false sharing entre
+/// o índice do produtor e o do consumidor
+#[repr(align(128))]
+struct CachePadded<T>(T);
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+/// Uma thread que pode estar estacionada esperando o outro lado do buffer
+#[derive(Default)]
+struct Waiter {
+    parked: AtomicBool,
+    thread: Mutex<Option<Thread>>,
+}
+
+impl Waiter {
+    /// Espera até `ready()` ser verdadeiro: primeiro girando e depois estacionando a thread
+    fn wait_until(&self, ready: impl Fn() -> bool) {
+        for _ in 0..SPIN_LIMIT {
+            if ready() {
+                return;
+            }
+            std::hint::spin_loop();
+        }
+
+        *self.thread.lock().unwrap() = Some(thread::current());
+        loop {
+            self.parked.store(true, Ordering::SeqCst);
+            fence(Ordering::SeqCst);
+
+            // Verifica de novo depois de anunciar que vai dormir, para não perder um wake()
+            if ready() {
+                self.parked.store(false, Ordering::Relaxed);
+                return;
+            }
+            thread::park();
+        }
+    }
+
+    /// Acorda a thread caso ela esteja estacionada
+    fn wake(&self) {
+        fence(Ordering::SeqCst);
+        if self.parked.swap(false, Ordering::SeqCst)
+            && let Some(thread) = self.thread.lock().unwrap().as_ref()
+        {
+            thread.unpark();
+        }
+    }
+}
+
+struct Shared<T> {
+    buffer: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    /// Próxima posição a ser lida (escrita apenas pelo consumidor)
+    head: CachePadded<AtomicUsize>,
+    /// Próxima posição a ser escrita (escrita apenas pelo produtor)
+    tail: CachePadded<AtomicUsize>,
+    /// Um dos lados foi descartado
+    closed: AtomicBool,
+    producer_waiter: Waiter,
+    consumer_waiter: Waiter,
+}
+
+// Os slots só são acessados pelo único produtor (entre tail e head + capacity) ou pelo único
+// consumidor (entre head e tail), e a passagem de posse é feita pelos stores Release/loads Acquire.
+// Isso depende de `Producer` e `Consumer` não serem `Sync`: com `&self` em `send` e `recv`,
+// duas threads com o mesmo handle escreveriam (ou leriam) o mesmo slot.
+unsafe impl<T: Send> Sync for Shared<T> {}
+unsafe impl<T: Send> Send for Shared<T> {}
+
+impl<T> Shared<T> {
+    fn slot(&self, index: usize) -> *mut MaybeUninit<T> {
+        self.buffer[index % self.buffer.len()].get()
+    }
+}
+
+impl<T> Drop for Shared<T> {
+    fn drop(&mut self) {
+        // Descarta os itens que ficaram no buffer
+        let tail = *self.tail.0.get_mut();
+        let mut head = *self.head.0.get_mut();
+        while head != tail {
+            unsafe { (*self.slot(head)).assume_init_drop() };
+            head = head.wrapping_add(1);
+        }
+    }
+}
+
+/// Lado produtor do ring buffer. Pode ser movido para outra thread, mas não compartilhado
+/// entre threads:
+///
+/// ```compile_fail
+/// let (producer, _consumer) = spigot_pi::spsc::channel::<i32>(4);
+/// std::thread::scope(|scope| {
+///     scope.spawn(|| producer.send(1));
+///     scope.spawn(|| producer.send(2));
+/// });
+/// ```
+pub struct Producer<T> {
+    shared: Arc<Shared<T>>,
+    /// Torna o handle `!Sync`, garantindo um único produtor
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+impl<T> Producer<T> {
+    /// Envia um item, esperando caso o buffer esteja cheio.
+    ///
+    /// Retorna o item de volta caso o consumidor tenha sido descartado.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let shared = &*self.shared;
+        let tail = shared.tail.load(Ordering::Relaxed);
+        let capacity = shared.buffer.len();
+
+        let has_space = || tail.wrapping_sub(shared.head.load(Ordering::Acquire)) < capacity;
+        let closed = || shared.closed.load(Ordering::Acquire);
+
+        if !has_space() {
+            shared.producer_waiter.wait_until(|| has_space() || closed());
+        }
+        if closed() {
+            return Err(SendError(value));
+        }
+
+        unsafe { (*shared.slot(tail)).write(value) };
+        shared.tail.store(tail.wrapping_add(1), Ordering::Release);
+        shared.consumer_waiter.wake();
+
+        Ok(())
+    }
+}
+
+impl<T: Send + 'static> Producer<T> {
+    /// Cria um handle que fecha o canal de fora, sem precisar do produtor ou do consumidor
+    pub fn closer(&self) -> Closer {
+        Closer { shared: self.shared.clone() }
+    }
+}
+
+impl<T> Drop for Producer<T> {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        self.shared.consumer_waiter.wake();
+    }
+}
+
+/// Lado consumidor do ring buffer. Pode ser movido para outra thread, mas não
+/// compartilhado entre threads:
+///
+/// ```compile_fail
+/// let (_producer, consumer) = spigot_pi::spsc::channel::<i32>(4);
+/// std::thread::scope(|scope| {
+///     scope.spawn(|| consumer.recv());
+///     scope.spawn(|| consumer.try_recv());
+/// });
+/// ```
+pub struct Consumer<T> {
+    shared: Arc<Shared<T>>,
+    /// Torna o handle `!Sync`, garantindo um único consumidor
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+impl<T> Consumer<T> {
+    /// Recebe um item, esperando caso o buffer esteja vazio.
+    ///
+    /// Retorna erro quando o produtor foi descartado e não há mais itens.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let shared = &*self.shared;
+        let head = shared.head.load(Ordering::Relaxed);
+
+        let has_item = || shared.tail.load(Ordering::Acquire) != head;
+        if !has_item() {
+            shared.consumer_waiter.wait_until(|| has_item() || shared.closed.load(Ordering::Acquire));
+        }
+
+        self.try_recv().map_err(|_| RecvError)
+    }
+
+    /// Tenta receber um item sem bloquear
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let shared = &*self.shared;
+        let head = shared.head.load(Ordering::Relaxed);
+
+        // closed é lido antes de tail: se o produtor fechou, tudo o que ele enviou já é visível
+        let closed = shared.closed.load(Ordering::Acquire);
+        if shared.tail.load(Ordering::Acquire) == head {
+            return Err(if closed { TryRecvError::Disconnected } else { TryRecvError::Empty });
+        }
+
+        let value = unsafe { (*shared.slot(head)).assume_init_read() };
+        shared.head.store(head.wrapping_add(1), Ordering::Release);
+        shared.producer_waiter.wake();
+
+        Ok(value)
+    }
+}
+
+impl<T> Iterator for Consumer<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.recv().ok()
+    }
+}
+
+impl<T> Drop for Consumer<T> {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        self.shared.producer_waiter.wake();
+    }
+}
+
+/// Handle que fecha um canal a partir de outra thread, criado por `Producer::closer`.
+///
+/// Depois de fechado, `send` falha e `recv` falha assim que o buffer esvazia, acordando
+/// qualquer um dos lados que esteja bloqueado.
+pub struct Closer {
+    shared: Arc<dyn Close + Send + Sync>,
+}
+
+impl Closer {
+    /// Fecha o canal
+    pub fn close(&self) {
+        self.shared.close();
+    }
+}
+
+trait Close {
+    fn close(&self);
+}
+
+impl<T> Close for Shared<T> {
+    fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+        self.producer_waiter.wake();
+        self.consumer_waiter.wake();
+    }
+}
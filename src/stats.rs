@@ -0,0 +1,85 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Estatísticas de execução de um estágio do pipeline (ou de um rank MPI)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StageStats {
+    /// Tempo processando carries sobre o chunk
+    pub busy: Duration,
+    /// Tempo bloqueado esperando o estágio anterior
+    pub recv_blocked: Duration,
+    /// Tempo bloqueado esperando o próximo estágio aceitar um lote
+    pub send_blocked: Duration,
+    /// Número de carries processados
+    pub carries: u64,
+    /// Número de células cedidas a estágios vizinhos pelo rebalanceamento
+    pub migrated_cells: u64,
+}
+
+/// Estatísticas de todos os estágios de uma computação, da esquerda para a direita
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PipelineStats {
+    pub stages: Vec<StageStats>,
+}
+
+impl PipelineStats {
+    /// Índice do estágio que passou mais tempo processando, o gargalo do pipeline
+    pub fn bottleneck(&self) -> Option<usize> {
+        (0..self.stages.len()).max_by_key(|&i| self.stages[i].busy)
+    }
+
+    /// Soma dos tempos e das migrações de todos os estágios. `carries` é o número de carries que passaram
+    /// pelo pipeline inteiro, ou seja, o do estágio que processou menos.
+    pub fn total(&self) -> StageStats {
+        StageStats {
+            busy: self.stages.iter().map(|stage| stage.busy).sum(),
+            recv_blocked: self.stages.iter().map(|stage| stage.recv_blocked).sum(),
+            send_blocked: self.stages.iter().map(|stage| stage.send_blocked).sum(),
+            carries: self.stages.iter().map(|stage| stage.carries).min().unwrap_or(0),
+            migrated_cells: self.stages.iter().map(|stage| stage.migrated_cells).sum(),
+        }
+    }
+
+    /// Relatório em texto com uma linha por estágio
+    pub fn report(&self) -> String {
+        let mut report = String::from("estágio       ocupado   esp. recv   esp. envio      carries\n");
+        for (i, stage) in self.stages.iter().enumerate() {
+            report += &format!(
+                "{:7} {:12.3?} {:11.3?} {:12.3?} {:12}{}\n",
+                i,
+                stage.busy,
+                stage.recv_blocked,
+                stage.send_blocked,
+                stage.carries,
+                if Some(i) == self.bottleneck() { "  <- gargalo" } else { "" },
+            );
+        }
+        report
+    }
+}
+
+/// Contadores de um estágio, atualizados pela thread do estágio e lidos por outras threads
+#[derive(Debug, Default)]
+pub(crate) struct StageCounters {
+    pub(crate) busy_ns: AtomicU64,
+    pub(crate) recv_wait_ns: AtomicU64,
+    pub(crate) send_wait_ns: AtomicU64,
+    pub(crate) carries: AtomicU64,
+    pub(crate) migrated_cells: AtomicU64,
+}
+
+impl StageCounters {
+    pub(crate) fn add(counter: &AtomicU64, elapsed: Duration) {
+        counter.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> StageStats {
+        StageStats {
+            busy: Duration::from_nanos(self.busy_ns.load(Ordering::Relaxed)),
+            recv_blocked: Duration::from_nanos(self.recv_wait_ns.load(Ordering::Relaxed)),
+            send_blocked: Duration::from_nanos(self.send_wait_ns.load(Ordering::Relaxed)),
+            carries: self.carries.load(Ordering::Relaxed),
+            migrated_cells: self.migrated_cells.load(Ordering::Relaxed),
+        }
+    }
+}
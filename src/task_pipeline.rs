@@ -0,0 +1,386 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::vec;
+use crate::cancellation::CancellationToken;
+use crate::partition::Partition;
+use crate::pi_digits_iter::PiDigitsIter;
+use crate::progress::{ProgressHook, ProgressIter};
+use crate::reciprocal::Reciprocal;
+use crate::spigot_cell::{init_cells, init_denominators, process_chunk_wavefront, CELL_BYTES};
+
+/// Executor onde as tarefas dos estágios do pipeline são agendadas.
+///
+/// Permite rodar o pipeline em um pool de threads que já existe na aplicação (como o
+/// pool do rayon) em vez de criar uma thread do sistema por estágio.
+pub trait Spawner: Send + Sync + 'static {
+    /// Agenda a tarefa para rodar em alguma thread do executor. Não deve bloquear.
+    fn spawn(&self, task: Box<dyn FnOnce() + Send + 'static>);
+}
+
+/// Permite compartilhar o mesmo executor entre várias computações
+impl<S: Spawner> Spawner for Arc<S> {
+    fn spawn(&self, task: Box<dyn FnOnce() + Send + 'static>) {
+        (**self).spawn(task)
+    }
+}
+
+type Task = Box<dyn FnOnce() + Send + 'static>;
+
+/// Pool fixo de threads que executa as tarefas em ordem de chegada.
+///
+/// As threads terminam quando o pool é descartado e a fila esvazia. Como o pipeline guarda
+/// o pool até a última tarefa terminar, um pool passado por valor vive exatamente o tempo
+/// da computação.
+pub struct WorkerPool {
+    shared: Arc<PoolShared>,
+}
+
+#[derive(Default)]
+struct PoolShared {
+    queue: Mutex<PoolQueue>,
+    available: Condvar,
+}
+
+#[derive(Default)]
+struct PoolQueue {
+    tasks: VecDeque<Task>,
+    shutdown: bool,
+}
+
+impl WorkerPool {
+    /// Cria um pool com `num_workers` threads (no mínimo 1)
+    pub fn new(num_workers: usize) -> Self {
+        let shared = Arc::new(PoolShared::default());
+        for _ in 0..num_workers.max(1) {
+            let shared = shared.clone();
+            thread::spawn(move || shared.run_worker());
+        }
+        Self { shared }
+    }
+}
+
+impl PoolShared {
+    fn run_worker(&self) {
+        loop {
+            let task = {
+                let mut queue = self.queue.lock().unwrap();
+                loop {
+                    if let Some(task) = queue.tasks.pop_front() {
+                        break task;
+                    }
+                    if queue.shutdown {
+                        return;
+                    }
+                    queue = self.available.wait(queue).unwrap();
+                }
+            };
+            task();
+        }
+    }
+}
+
+impl Spawner for WorkerPool {
+    fn spawn(&self, task: Task) {
+        self.shared.queue.lock().unwrap().tasks.push_back(task);
+        self.shared.available.notify_one();
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().shutdown = true;
+        self.shared.available.notify_all();
+    }
+}
+
+/// Opções de execução do pipeline baseado em tarefas
+#[derive(Debug, Clone)]
+pub struct TaskOptions {
+    /// Número de estágios (chunks) do pipeline. Pode ser maior que o número de threads do
+    /// executor: cada estágio só ocupa uma thread enquanto tem lotes para processar.
+    pub num_stages: usize,
+    /// Número de carries em cada lote que trafega entre os estágios
+    pub carry_batch: usize,
+    /// Número de células de cada bloco (tile) da varredura em frente de onda
+    pub tile_len: usize,
+    /// Número máximo de lotes em trânsito no pipeline. Novos triggers só entram no pipeline
+    /// quando o iterador consome os lotes que saem dele, o que limita a memória usada.
+    pub max_in_flight: usize,
+    /// Token para interromper a computação. Descartar o iterador também interrompe.
+    pub cancellation: CancellationToken,
+    /// Callback de progresso. O tempo ocioso de cada estágio é o tempo em que ele não
+    /// estava processando lotes.
+    pub progress: Option<ProgressHook>,
+}
+
+impl Default for TaskOptions {
+    fn default() -> Self {
+        Self {
+            num_stages: thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            carry_batch: 16,
+            // 32 KiB de células por tile
+            tile_len: 32 * 1024 / CELL_BYTES,
+            max_in_flight: 256,
+            cancellation: CancellationToken::new(),
+            progress: None,
+        }
+    }
+}
+
+/// Calcula os dígitos de PI com os estágios do pipeline rodando como tarefas no `spawner`.
+///
+/// Cada estágio guarda o seu chunk e uma fila de lotes de carries. Quando um lote chega em
+/// um estágio que não está agendado, uma tarefa é criada para ele; a tarefa processa os
+/// lotes da fila em ordem e entrega cada resultado ao estágio da esquerda. Como cada estágio
+/// tem no máximo uma tarefa ativa, a ordem dos lotes é preservada sem travar threads do
+/// executor esperando por mensagens.
+///
+/// Os triggers são injetados pelo próprio iterador, conforme os lotes são consumidos, então o
+/// iterador não deve ser consumido de dentro de uma tarefa do mesmo executor.
+///
+/// # Parâmetros
+/// - `n_digits`: Número de dígitos de PI a serem calculados
+/// - `spawner`: Executor onde as tarefas serão agendadas
+/// - `options`: Opções do pipeline
+///
+/// # Retorna
+/// Um iterador sobre os dígitos de PI (u8)
+pub fn calculate_pi_with_spawner<S: Spawner>(n_digits: usize, spawner: S, options: TaskOptions) -> impl Iterator<Item = u8> {
+    let total_len = (n_digits * 10) / 3;
+    let num_stages = options.num_stages.max(1);
+
+    let (output_tx, output_rx) = channel();
+
+    // Token desta computação. No cancelamento um lote vazio acorda o iterador, que pode estar
+    // bloqueado esperando por um lote que nunca vai chegar.
+    let cancellation = options.cancellation.child_token();
+    let wake_output = output_tx.clone();
+    cancellation.on_cancel(move || {
+        let _ = wake_output.send(Vec::new());
+    });
+
+    let pipeline = Arc::new(TaskPipeline {
+        stages: Partition::balanced(total_len, num_stages)
+            .iter()
+            .map(|(start_global_index, range)| TaskStage::new(start_global_index..start_global_index + range.len()))
+            .collect(),
+        spawner,
+        output: output_tx,
+        tile_len: options.tile_len,
+        cancellation,
+    });
+
+    let start = Instant::now();
+    let stats_pipeline = pipeline.clone();
+    let stage_idle = move || {
+        let elapsed = start.elapsed();
+        stats_pipeline.stages.iter()
+            .map(|stage| elapsed.saturating_sub(Duration::from_nanos(stage.busy_ns.load(Ordering::Relaxed))))
+            .collect()
+    };
+
+    let mut source = TaskPipelineOutput {
+        pipeline,
+        output: output_rx,
+        current: Vec::new().into_iter(),
+        carry_batch: options.carry_batch.max(1),
+        triggers_left: n_digits,
+        in_flight: 0,
+    };
+    for _ in 0..options.max_in_flight.max(1) {
+        source.inject_trigger();
+    }
+
+    ProgressIter::new(PiDigitsIter::new(source), n_digits, options.progress, stage_idle)
+}
+
+/// Calcula os dígitos de PI dividindo o array em `num_stages` estágios agendados em um pool
+/// fixo de `num_workers` threads.
+///
+/// Diferente de `calculate_pi_parallel`, o número de estágios não depende do número de
+/// threads: com mais estágios que threads, cada thread pega o próximo estágio que tem
+/// carries para processar, o que equilibra a carga mesmo quando os chunks têm custos
+/// diferentes, e permite usar muitos estágios em máquinas com poucos cores.
+///
+/// # Parâmetros
+/// - `n_digits`: Número de dígitos de PI a serem calculados
+/// - `num_stages`: Número de estágios (chunks) do pipeline
+/// - `num_workers`: Número de threads do pool
+///
+/// # Retorna
+/// Um iterador sobre os dígitos de PI (u8)
+pub fn calculate_pi_tasks(n_digits: usize, num_stages: usize, num_workers: usize) -> impl Iterator<Item = u8> {
+    calculate_pi_with_spawner(n_digits, WorkerPool::new(num_workers), TaskOptions {
+        num_stages,
+        ..TaskOptions::default()
+    })
+}
+
+/// Estado compartilhado por todas as tarefas do pipeline
+struct TaskPipeline<S> {
+    /// Estágios da esquerda para a direita (o último recebe os triggers)
+    stages: Vec<TaskStage>,
+    spawner: S,
+    /// Recebe os lotes que saem do estágio 0
+    output: Sender<Vec<i32>>,
+    tile_len: usize,
+    /// Depois de cancelado, as tarefas descartam os lotes restantes e liberam os chunks
+    cancellation: CancellationToken,
+}
+
+struct TaskStage {
+    cells: Range<usize>,
+    /// Células do estágio, alocadas pela primeira tarefa que processar um lote (first-touch)
+    chunk: Mutex<StageCells>,
+    inbox: Mutex<Inbox>,
+    /// Tempo acumulado processando lotes
+    busy_ns: AtomicU64,
+}
+
+/// Valores das células de um estágio e os recíprocos dos seus denominadores
+#[derive(Default)]
+struct StageCells {
+    values: Vec<i32>,
+    dens: Vec<Reciprocal>,
+}
+
+#[derive(Default)]
+struct Inbox {
+    batches: VecDeque<Vec<i32>>,
+    /// Existe uma tarefa agendada ou rodando para este estágio
+    scheduled: bool,
+}
+
+impl TaskStage {
+    fn new(cells: Range<usize>) -> Self {
+        Self { cells, chunk: Mutex::default(), inbox: Mutex::default(), busy_ns: AtomicU64::new(0) }
+    }
+}
+
+impl<S: Spawner> TaskPipeline<S> {
+    /// Coloca um lote na fila do estágio, agendando uma tarefa para ele se necessário
+    fn push(self: &Arc<Self>, stage: usize, batch: Vec<i32>) {
+        let mut inbox = self.stages[stage].inbox.lock().unwrap();
+        inbox.batches.push_back(batch);
+        if !inbox.scheduled {
+            inbox.scheduled = true;
+            drop(inbox);
+
+            let pipeline = self.clone();
+            self.spawner.spawn(Box::new(move || pipeline.run_stage(stage)));
+        }
+    }
+
+    /// Libera as células e os lotes pendentes de todos os estágios depois do cancelamento
+    fn release(&self) {
+        for stage in &self.stages {
+            *stage.chunk.lock().unwrap() = StageCells::default();
+            stage.inbox.lock().unwrap().batches.clear();
+        }
+    }
+
+    /// Corpo da tarefa de um estágio: processa os lotes da fila até ela esvaziar
+    fn run_stage(self: &Arc<Self>, index: usize) {
+        let stage = &self.stages[index];
+        let mut chunk = stage.chunk.lock().unwrap();
+        if chunk.values.len() != stage.cells.len() && !self.cancellation.is_cancelled() {
+            *chunk = StageCells { values: init_cells(stage.cells.clone()), dens: init_denominators(stage.cells.clone()) };
+        }
+
+        loop {
+            let mut batch = {
+                let mut inbox = stage.inbox.lock().unwrap();
+                if self.cancellation.is_cancelled() {
+                    inbox.batches.clear();
+                    *chunk = StageCells::default();
+                }
+                match inbox.batches.pop_front() {
+                    Some(batch) => batch,
+                    None => {
+                        inbox.scheduled = false;
+                        return;
+                    }
+                }
+            };
+
+            let busy_since = Instant::now();
+            let StageCells { values, dens } = &mut *chunk;
+            process_chunk_wavefront(values, dens, stage.cells.start, &mut batch, self.tile_len);
+            stage.busy_ns.fetch_add(busy_since.elapsed().as_nanos() as u64, Ordering::Relaxed);
+
+            match index {
+                0 => {
+                    if self.output.send(batch).is_err() {
+                        self.cancellation.cancel();
+                    }
+                }
+                _ => self.push(index - 1, batch),
+            }
+        }
+    }
+}
+
+/// Fonte dos dígitos brutos: consome os lotes que saem do pipeline e injeta novos triggers
+struct TaskPipelineOutput<S: Spawner> {
+    pipeline: Arc<TaskPipeline<S>>,
+    output: Receiver<Vec<i32>>,
+    current: vec::IntoIter<i32>,
+    carry_batch: usize,
+    triggers_left: usize,
+    in_flight: usize,
+}
+
+impl<S: Spawner> TaskPipelineOutput<S> {
+    fn inject_trigger(&mut self) {
+        if self.triggers_left == 0 {
+            return;
+        }
+        let batch_len = self.triggers_left.min(self.carry_batch);
+        self.triggers_left -= batch_len;
+        self.in_flight += 1;
+
+        let last_stage = self.pipeline.stages.len() - 1;
+        self.pipeline.push(last_stage, vec![0; batch_len]);
+    }
+}
+
+impl<S: Spawner> Iterator for TaskPipelineOutput<S> {
+    type Item = i32;
+
+    fn next(&mut self) -> Option<i32> {
+        loop {
+            if let Some(digit) = self.current.next() {
+                return Some(digit);
+            }
+            if self.in_flight == 0 {
+                return None;
+            }
+
+            let batch = self.output.recv().ok()?;
+            if self.pipeline.cancellation.is_cancelled() {
+                self.pipeline.release();
+                self.in_flight = 0;
+                self.triggers_left = 0;
+                return None;
+            }
+            // Lotes vazios só servem para acordar o iterador no cancelamento
+            if batch.is_empty() {
+                continue;
+            }
+            self.current = batch.into_iter();
+            self.in_flight -= 1;
+            self.inject_trigger();
+        }
+    }
+}
+
+impl<S: Spawner> Drop for TaskPipelineOutput<S> {
+    fn drop(&mut self) {
+        self.pipeline.cancellation.cancel();
+    }
+}
@@ -1,4 +1,18 @@
-use crate::{calculate_pi_sequential, calculate_pi_parallel};
+use crate::{calculate_pi_sequential, calculate_pi_parallel, calculate_pi_parallel_with, ParallelOptions};
+use crate::{calculate_pi_tasks, calculate_pi_with_spawner, CancellationToken, Spawner, TaskOptions};
+use crate::{Progress, ProgressHook};
+use crate::{estimate_with_calibration, Backend, Calibration, CellType};
+use crate::{fnv1a64, OutputManifest};
+use crate::{calculate_pi_in_memory, DistributedOptions, InMemoryTransport, RankFailure};
+use crate::distributed::{rank0_coordinator, rank0_to_file, rank_worker, Coordinator};
+use crate::output::BlockOutput;
+use crate::{run_socket_worker, SocketCoordinator};
+use crate::balanced_chunks_mut::BalancedChunksMut;
+use crate::partition::Partition;
+use crate::parallel_pi::{cells_kept_on_request, migration_len};
+use crate::reciprocal::Reciprocal;
+use crate::spsc;
+use crate::spigot_cell::{init_cells, init_denominators, process_chunk, process_chunk_wavefront};
 use std::fs::File;
 use std::io::{BufReader, Read};
 
@@ -11,27 +25,37 @@ fn read_expected_digits(n: usize) -> impl Iterator<Item = u8> {
     reader
         .bytes()
         .map(|b| b.expect("Erro ao ler byte do arquivo"))
-        .filter(|&b| b >= b'0' && b <= b'9')
+        .filter(|b| b.is_ascii_digit())
         .map(|b| b - b'0')
         .take(n)
 }
 
 /// Verifica se os dígitos calculados correspondem aos dígitos esperados
-/// Compara dígito a dígito e dispara panic com assert_eq! em caso de divergência
-fn verify_pi_digits<I1, I2>(expected: I1, actual: I2)
+/// Compara dígito a dígito e dispara panic com assert_eq! em caso de divergência ou se
+/// uma das sequências terminar antes da outra
+fn verify_pi_digits<I1, I2>(mut expected: I1, mut actual: I2)
 where
     I1: Iterator<Item = u8>,
     I2: Iterator<Item = u8>,
 {
-    for (pos, (expected_digit, actual_digit)) in expected.zip(actual).enumerate() {
-        assert_eq!(
-            expected_digit,
-            actual_digit,
-            "Dígito incorreto na posição {}: esperado {}, encontrado {}",
-            pos,
-            expected_digit,
-            actual_digit
-        );
+    for pos in 0.. {
+        match (expected.next(), actual.next()) {
+            (Some(expected_digit), Some(actual_digit)) => assert_eq!(
+                expected_digit,
+                actual_digit,
+                "Dígito incorreto na posição {}: esperado {}, encontrado {}",
+                pos,
+                expected_digit,
+                actual_digit
+            ),
+            (None, None) => break,
+            (expected_digit, actual_digit) => panic!(
+                "Quantidade de dígitos incorreta: na posição {} esperado {:?}, encontrado {:?}",
+                pos,
+                expected_digit,
+                actual_digit
+            ),
+        }
     }
 }
 
@@ -54,3 +78,548 @@ fn test_pi_parallel_verification() {
     let actual = calculate_pi_parallel(n_digits, num_threads, channel_bound);
     verify_pi_digits(expected, actual);
 }
+
+#[test]
+fn test_reciprocal_div_rem() {
+    // Denominadores reais do Spigot (10 e 2i+1) e alguns casos extremos de 32 bits
+    let divisors = [2u32, 3, 7, 10, 21, 2 * 33_333 + 1, 641, 65_537, u32::MAX / 2, u32::MAX];
+    let numerators = (0u32..10_000)
+        .chain([u32::MAX, u32::MAX - 1, i32::MAX as u32, 1 << 31, 123_456_789]);
+
+    for n in numerators {
+        for &d in &divisors {
+            let reciprocal = Reciprocal::new(d);
+            assert_eq!(reciprocal.div_rem(n), (n / d, n % d), "Falha para {} / {}", n, d);
+        }
+    }
+}
+
+#[test]
+fn test_wavefront_matches_digit_by_digit() {
+    let start_global_index = 1000;
+    let cells = start_global_index..start_global_index + 257;
+    let dens = init_denominators(cells.clone());
+    let mut expected = init_cells(cells);
+    let mut actual = expected.clone();
+
+    let carries_in = [0, 3, 17, 0, 9, 1, 25, 4];
+    let expected_out = carries_in
+        .iter()
+        .map(|&carry| process_chunk(&mut expected, &dens, start_global_index, carry))
+        .collect::<Vec<i32>>();
+
+    let mut actual_out = carries_in.to_vec();
+    process_chunk_wavefront(&mut actual, &dens, start_global_index, &mut actual_out, 10);
+
+    assert_eq!(expected_out, actual_out);
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn test_pi_parallel_wavefront_verification() {
+    let n_digits = 2000;
+    let options = ParallelOptions {
+        num_threads: 3,
+        channel_bound: 16,
+        carry_batch: 3,
+        tile_len: 7,
+        wavefront_depth: 5,
+        rebalance_interval: 0,
+        ..ParallelOptions::default()
+    };
+    let expected = read_expected_digits(n_digits);
+    let actual = calculate_pi_parallel_with(n_digits, options);
+    verify_pi_digits(expected, actual);
+}
+
+#[test]
+fn test_pi_parallel_pinned_verification() {
+    // Mais estágios que cores, para que alguns cores recebam mais de um estágio. Sem a
+    // feature `affinity` a opção é ignorada e o resultado tem que ser o mesmo.
+    let n_digits = 2000;
+    let num_threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1) + 1;
+    let options = ParallelOptions { num_threads, pin_threads: true, ..ParallelOptions::default() };
+    let expected = read_expected_digits(n_digits);
+    let actual = calculate_pi_parallel_with(n_digits, options);
+    verify_pi_digits(expected, actual);
+}
+
+#[test]
+fn test_spsc_channel_preserves_order() {
+    let (tx, rx) = spsc::channel::<usize>(4);
+    let producer = std::thread::spawn(move || {
+        for i in 0..100_000 {
+            tx.send(i).expect("Consumidor descartado antes do fim");
+        }
+    });
+
+    // Após o produtor terminar o consumidor recebe tudo e depois encerra
+    assert!(rx.eq(0..100_000));
+    producer.join().unwrap();
+}
+
+#[test]
+fn test_spsc_channel_disconnect() {
+    let (tx, rx) = spsc::channel::<i32>(2);
+    tx.send(1).unwrap();
+    drop(tx);
+    assert_eq!(rx.recv(), Ok(1));
+    assert!(rx.recv().is_err());
+
+    let (tx, rx) = spsc::channel::<i32>(2);
+    drop(rx);
+    assert!(tx.send(1).is_err());
+}
+
+#[test]
+fn test_pi_parallel_rebalance_verification() {
+    let n_digits = 3000;
+    // Buffers pequenos e rebalanceamento frequente para forçar várias migrações de células
+    let options = ParallelOptions {
+        num_threads: 4,
+        channel_bound: 2,
+        carry_batch: 4,
+        wavefront_depth: 8,
+        rebalance_interval: 8,
+        ..ParallelOptions::default()
+    };
+    let expected = read_expected_digits(n_digits);
+    let mut actual = calculate_pi_parallel_with(n_digits, options);
+    verify_pi_digits(expected, actual.by_ref());
+
+    // Depois do fim as estatísticas são finais e mostram que houve migração
+    assert_eq!(actual.next(), None);
+    assert!(actual.stats().total().migrated_cells > 0, "Nenhuma célula foi migrada");
+}
+
+#[test]
+fn test_pi_parallel_rebalance_small_chunks() {
+    // Mais estágios que células: alguns estágios começam com o chunk vazio
+    let n_digits = 30;
+    let options = ParallelOptions {
+        num_threads: 128,
+        channel_bound: 1,
+        carry_batch: 1,
+        rebalance_interval: 1,
+        ..ParallelOptions::default()
+    };
+    let expected = read_expected_digits(n_digits);
+    let actual = calculate_pi_parallel_with(n_digits, options);
+    assert!(expected.eq(actual));
+}
+
+#[test]
+fn test_rebalance_migration_len() {
+    // Metade da fração de espera, limitada a 1/8 do chunk
+    assert_eq!(migration_len(1000, 0.1), 50);
+    assert_eq!(migration_len(1000, 0.9), 125);
+    assert_eq!(migration_len(0, 0.9), 0);
+
+    // Quem recebe um pedido sempre fica com pelo menos uma célula, se tiver alguma
+    assert_eq!(cells_kept_on_request(10, 3), 7);
+    assert_eq!(cells_kept_on_request(10, 50), 1);
+    assert_eq!(cells_kept_on_request(1, 1), 1);
+    assert_eq!(cells_kept_on_request(0, 5), 0);
+}
+
+/// Tamanhos dos chunks de um slice de `len` elementos, conferindo que a ordem direta e a
+/// ordem reversa produzem a mesma divisão
+fn chunk_sizes(len: usize, chunks: impl for<'a> Fn(&'a mut [u8]) -> BalancedChunksMut<'a, u8>) -> Vec<usize> {
+    let mut data = vec![0u8; len];
+    let forward = chunks(&mut data).map(|c| c.len()).collect::<Vec<_>>();
+    let mut backward = chunks(&mut data).rev().map(|c| c.len()).collect::<Vec<_>>();
+    backward.reverse();
+    assert_eq!(forward, backward);
+    assert_eq!(chunks(&mut data).len(), forward.len());
+    forward
+}
+
+#[test]
+fn test_balanced_chunks_sizes() {
+    assert_eq!(chunk_sizes(10, |s| BalancedChunksMut::new(s, 3)), [4, 3, 3]);
+    assert_eq!(chunk_sizes(10, |s| BalancedChunksMut::new(s, 4)), [3, 3, 2, 2]);
+    assert!(chunk_sizes(10, |s| BalancedChunksMut::new(s, 0)).is_empty());
+}
+
+#[test]
+fn test_weighted_chunks_sizes() {
+    // Capacidades 1:2:1 → 25, 50, 25 elementos
+    assert_eq!(chunk_sizes(100, |s| BalancedChunksMut::with_weights(s, &[1.0, 2.0, 1.0])), [25, 50, 25]);
+
+    // Custo crescente: os chunks da direita ficam menores
+    let sizes = chunk_sizes(100, |s| BalancedChunksMut::with_cost(s, 4, |i| (i + 1) as f64));
+    assert_eq!(sizes.iter().sum::<usize>(), 100);
+    assert!(sizes.windows(2).all(|w| w[0] > w[1]), "{:?}", sizes);
+
+    // Cada chunk fica a menos de um elemento do custo ideal
+    let mut start = 0;
+    for size in sizes {
+        let cost: usize = (start + 1..=start + size).sum();
+        assert!(cost.abs_diff(5050 / 4) <= start + size + 1, "Custo {} longe do ideal", cost);
+        start += size;
+    }
+}
+
+#[test]
+fn test_partition_global_starts() {
+    // Chunk local começando no índice global 1000, dividido em 3 partes (4, 3, 3)
+    let partition = Partition::balanced(10, 3).with_offset(1000);
+    let parts: Vec<_> = partition.iter().collect();
+    assert_eq!(parts, [(1000, 0..4), (1004, 4..7), (1007, 7..10)]);
+
+    // O iterador de slices produz os mesmos inícios globais, nos dois sentidos
+    let mut data = [0u8; 10];
+    let starts: Vec<_> = BalancedChunksMut::from_partition(&mut data, partition.clone())
+        .with_global_starts()
+        .rev()
+        .map(|(start, chunk)| (start, chunk.len()))
+        .collect();
+    assert_eq!(starts, [(1007, 3), (1004, 3), (1000, 4)]);
+}
+
+/// Executor mínimo para os testes: uma thread do sistema por tarefa
+struct ThreadPerTask;
+
+impl Spawner for ThreadPerTask {
+    fn spawn(&self, task: Box<dyn FnOnce() + Send + 'static>) {
+        std::thread::spawn(task);
+    }
+}
+
+#[test]
+fn test_pi_task_pipeline_verification() {
+    let n_digits = 3000;
+    // Poucos lotes em trânsito para exercitar a injeção de triggers pelo iterador
+    let options = TaskOptions {
+        num_stages: 6,
+        carry_batch: 8,
+        max_in_flight: 3,
+        ..TaskOptions::default()
+    };
+    let expected = read_expected_digits(n_digits);
+    let actual = calculate_pi_with_spawner(n_digits, ThreadPerTask, options);
+    verify_pi_digits(expected, actual);
+}
+
+#[test]
+fn test_pi_tasks_more_stages_than_workers() {
+    let n_digits = 3000;
+    let expected = read_expected_digits(n_digits);
+    let actual = calculate_pi_tasks(n_digits, 16, 2);
+    verify_pi_digits(expected, actual);
+}
+
+#[test]
+fn test_cancellation_stops_engines() {
+    let n_digits = 20000;
+
+    let cancellation = CancellationToken::new();
+    let options = ParallelOptions { num_threads: 3, cancellation: cancellation.clone(), ..ParallelOptions::default() };
+    let mut digits = calculate_pi_parallel_with(n_digits, options);
+    assert_eq!(digits.next(), Some(3));
+    cancellation.cancel();
+    // Só os lotes que já estavam nos canais ainda são entregues
+    assert!(digits.count() < n_digits - 1);
+
+    let cancellation = CancellationToken::new();
+    let options = TaskOptions { num_stages: 4, cancellation: cancellation.clone(), ..TaskOptions::default() };
+    let mut digits = calculate_pi_with_spawner(n_digits, ThreadPerTask, options);
+    assert_eq!(digits.next(), Some(3));
+    cancellation.cancel();
+    assert!(digits.count() < n_digits - 1);
+}
+
+#[test]
+fn test_child_tokens() {
+    let parent = CancellationToken::new();
+
+    // Muitos filhos de curta duração (um por computação) não afetam o pai
+    for _ in 0..10_000 {
+        parent.child_token().cancel();
+    }
+    assert!(!parent.is_cancelled());
+
+    let children = (0..3).map(|_| parent.child_token()).collect::<Vec<_>>();
+    parent.cancel();
+    assert!(children.iter().all(CancellationToken::is_cancelled));
+    assert!(parent.child_token().is_cancelled());
+}
+
+#[test]
+fn test_progress_reports() {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    let n_digits = 1000;
+    let reports: Arc<Mutex<Vec<Progress>>> = Arc::default();
+    let sink = reports.clone();
+    let options = TaskOptions {
+        num_stages: 3,
+        progress: Some(ProgressHook::new(Duration::ZERO, move |p| sink.lock().unwrap().push(p.clone()))),
+        ..TaskOptions::default()
+    };
+    let digits: Vec<u8> = calculate_pi_with_spawner(n_digits, ThreadPerTask, options).collect();
+    verify_pi_digits(read_expected_digits(n_digits), digits.into_iter());
+
+    // Um relatório por dígito mais o relatório final
+    let reports = reports.lock().unwrap();
+    assert_eq!(reports.len(), n_digits + 1);
+    assert!(reports.windows(2).all(|w| w[0].digits_emitted <= w[1].digits_emitted));
+
+    let last = reports.last().unwrap();
+    assert_eq!(last.digits_emitted, n_digits);
+    assert_eq!(last.eta, Some(Duration::ZERO));
+    assert_eq!(last.stage_idle.len(), 3);
+}
+
+#[test]
+fn test_parallel_stage_stats() {
+    let n_digits = 1000;
+    let options = ParallelOptions { num_threads: 3, carry_batch: 10, ..ParallelOptions::default() };
+    let mut digits = calculate_pi_parallel_with(n_digits, options);
+    assert_eq!(digits.by_ref().count(), n_digits);
+
+    // Todo estágio processa todos os carries
+    let stats = digits.stats();
+    assert_eq!(stats.stages.len(), 3);
+    assert!(stats.stages.iter().all(|stage| stage.carries == n_digits as u64));
+    assert!(stats.bottleneck().is_some());
+}
+
+#[test]
+fn test_estimate_limits() {
+    let calibration = Calibration { ns_per_cell_update: 1.0 };
+
+    let small = estimate_with_calibration(10000, Backend::Parallel, 4, calibration);
+    assert_eq!(small.array_len, 33333);
+    assert_eq!(small.cell_type, CellType::I32);
+    assert!(small.fits_pod_limit() && small.warnings.is_empty());
+
+    // 20 milhões de dígitos com -np 3: células i64, mas a janela de crédito mantém os
+    // buffers pequenos e cada rank cabe em 512Mi
+    let large = estimate_with_calibration(20_000_000, Backend::Mpi, 3, calibration);
+    assert_eq!(large.num_parts, 3);
+    assert_eq!(large.cell_type, CellType::I64);
+    assert!(large.fits_pod_limit());
+    assert_eq!(large.warnings.len(), 1);
+
+    // Com o dobro de dígitos o chunk de cada rank passa do limite
+    let huge = estimate_with_calibration(40_000_000, Backend::Mpi, 3, calibration);
+    assert!(!huge.fits_pod_limit());
+    assert_eq!(huge.warnings.len(), 2);
+
+    // Mais ranks reduzem a memória de cada um e o tempo estimado
+    let wider = estimate_with_calibration(20_000_000, Backend::Mpi, 9, calibration);
+    assert!(wider.bytes_per_chunk < large.bytes_per_chunk);
+    assert!(wider.time < large.time);
+}
+
+#[test]
+fn test_output_manifest() {
+    // Vetores de teste do FNV-1a de 64 bits
+    assert_eq!(fnv1a64(b""), 0xcbf29ce484222325);
+    assert_eq!(fnv1a64(b"a"), 0xaf63dc4c8601ec8c);
+
+    let digits = calculate_pi_sequential(50).map(|d| b'0' + d).collect::<Vec<u8>>();
+    let manifest = OutputManifest::new("pi.txt", &digits);
+    assert_eq!(manifest.digits, 0..50);
+    assert_eq!(manifest.manifest_path(), std::path::PathBuf::from("pi.txt.manifest"));
+    assert!(manifest.to_string().contains(&format!("hash: fnv1a64:{:016x}", fnv1a64(&digits))));
+}
+
+#[test]
+fn test_pi_in_memory_verification() {
+    let n_digits = 3000;
+    // Janela pequena para exercitar os créditos; com um rank o rank 0 calcula sozinho
+    for ranks in [1, 3] {
+        let options = DistributedOptions { num_threads: 2, carry_batch: 8, max_in_flight: 64, ..DistributedOptions::default() };
+        let mut digits = calculate_pi_in_memory(n_digits, ranks, options);
+        let actual = digits.by_ref().collect::<Vec<u8>>();
+        verify_pi_digits(read_expected_digits(n_digits), actual.into_iter());
+
+        assert_eq!(digits.failure(), None);
+        let stats = digits.stats();
+        assert_eq!(stats.stages.len(), ranks);
+        assert!(stats.stages.iter().all(|rank| rank.carries == n_digits as u64));
+    }
+}
+
+#[test]
+fn test_distributed_dead_rank_timeout() {
+    use std::sync::mpsc::channel;
+    use std::time::{Duration, Instant};
+
+    // O rank 1 nunca roda: o rank 0 precisa abortar em vez de esperar para sempre
+    let mut transports = InMemoryTransport::create(2);
+    transports.truncate(1);
+    let timeout = Duration::from_millis(500);
+    let options = DistributedOptions { num_threads: 1, receive_timeout: timeout, ..DistributedOptions::default() };
+    let coordinator = Coordinator::default();
+    let (tx, rx) = channel();
+    let started = Instant::now();
+    rank0_coordinator(&transports[0], 100, &options, tx, &coordinator);
+
+    // Sem esperar de novo pelas estatísticas do rank que não respondeu
+    assert!(started.elapsed() < timeout * 2, "O rank 0 demorou {:?}", started.elapsed());
+    assert_eq!(rx.iter().count(), 0);
+    let failure = coordinator.failure.lock().unwrap().unwrap();
+    assert_eq!(failure, RankFailure::Timeout { rank: 1, timeout });
+    assert_eq!(failure.to_string(), "o rank 1 não respondeu em 500ms");
+}
+
+/// Roda `rank0_to_file` com `ranks` ranks em memória, gravando com `write_at`
+fn run_rank0_to_file(
+    n_digits: usize,
+    ranks: usize,
+    block_len: usize,
+    write_at: impl FnMut(u64, &[u8]) -> std::io::Result<()>,
+) -> std::io::Result<OutputManifest> {
+    let mut transports = InMemoryTransport::create(ranks);
+    let rank0 = transports.remove(0);
+    let options = &DistributedOptions { num_threads: 2, carry_batch: 8, ..DistributedOptions::default() };
+    std::thread::scope(|scope| {
+        for transport in transports {
+            scope.spawn(move || rank_worker(&transport, n_digits, options));
+        }
+        let mut output = BlockOutput::new("pi.txt", write_at);
+        rank0_to_file(&rank0, n_digits, options, &Coordinator::default(), &mut output, block_len)
+    })
+}
+
+#[test]
+fn test_distributed_block_output() {
+    let n_digits = 1000;
+    let block_len = 64;
+
+    // Cada bloco é gravado logo depois do anterior, em ordem
+    let mut file = Vec::new();
+    let mut offsets = Vec::new();
+    let manifest = run_rank0_to_file(n_digits, 3, block_len, |offset, block| {
+        offsets.push(offset);
+        file.resize(file.len().max(offset as usize + block.len()), 0);
+        file[offset as usize..][..block.len()].copy_from_slice(block);
+        Ok(())
+    })
+    .unwrap();
+
+    assert_eq!(offsets, (0..n_digits as u64).step_by(block_len).collect::<Vec<_>>());
+    let expected = read_expected_digits(n_digits).map(|digit| b'0' + digit).collect::<Vec<u8>>();
+    assert_eq!(file, expected);
+    assert_eq!(manifest, OutputManifest::new("pi.txt", &expected));
+
+    // Um erro de escrita interrompe a computação e é retornado
+    let result = run_rank0_to_file(n_digits, 3, block_len, |offset, _| match offset {
+        0 => Ok(()),
+        _ => Err(std::io::Error::other("disco cheio")),
+    });
+    assert_eq!(result.unwrap_err().to_string(), "disco cheio");
+}
+
+#[test]
+fn test_pi_socket_verification() {
+    let n_digits = 2000;
+    let workers = 2;
+
+    // Cada endereço de escuta por rank: TCP em portas escolhidas pelo sistema e sockets Unix
+    let mut listens = vec![vec!["127.0.0.1:0".to_string(); workers + 1]];
+    if cfg!(unix) {
+        let dir = std::env::temp_dir();
+        let path = |rank| {
+            // Um arquivo de socket sem ninguém escutando, como o de um processo que caiu, é
+            // substituído pelo bind
+            let path = dir.join(format!("spigot-{}-{}.sock", std::process::id(), rank));
+            #[cfg(unix)]
+            drop(std::os::unix::net::UnixListener::bind(&path));
+            format!("unix:{}", path.display())
+        };
+        listens.push((0..=workers).map(path).collect());
+    }
+
+    for listen in listens {
+        let coordinator = SocketCoordinator::bind(&listen[0].parse().unwrap()).unwrap();
+        let address = coordinator.local_address().unwrap();
+        if listen[0].starts_with("unix:") {
+            // Um socket em uso não é substituído
+            let error = SocketCoordinator::bind(&address).err().unwrap();
+            assert_eq!(error.kind(), std::io::ErrorKind::AddrInUse);
+        }
+        let handles = listen[1..]
+            .iter()
+            .map(|listen| {
+                let (address, listen) = (address.clone(), listen.parse().unwrap());
+                let options = DistributedOptions { num_threads: 2, ..DistributedOptions::default() };
+                std::thread::spawn(move || run_socket_worker(&address, &listen, options))
+            })
+            .collect::<Vec<_>>();
+
+        let options = DistributedOptions { num_threads: 2, carry_batch: 16, max_in_flight: 128, ..DistributedOptions::default() };
+        let mut digits = coordinator.run(n_digits, workers, options).unwrap();
+        let actual = digits.by_ref().collect::<Vec<u8>>();
+        verify_pi_digits(read_expected_digits(n_digits), actual.into_iter());
+        assert_eq!(digits.failure(), None);
+        assert!(digits.stats().stages.iter().all(|rank| rank.carries == n_digits as u64));
+        for handle in handles {
+            assert_eq!(handle.join().unwrap().unwrap(), None);
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_pi_rayon_verification() {
+    use std::sync::Arc;
+
+    let n_digits = 3000;
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    let options = TaskOptions { num_stages: 8, ..TaskOptions::default() };
+    let expected = read_expected_digits(n_digits);
+    let actual = crate::calculate_pi_rayon_with(n_digits, Some(Arc::new(pool)), options);
+    verify_pi_digits(expected, actual);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_balanced_chunks_par_iter() {
+    use rayon::prelude::*;
+
+    // Cada chunk é preenchido com o seu índice, em paralelo
+    let mut data = [0usize; 10];
+    BalancedChunksMut::new(&mut data, 4)
+        .into_par_iter()
+        .enumerate()
+        .with_min_len(1)
+        .for_each(|(i, chunk)| chunk.fill(i));
+    assert_eq!(data, [0, 0, 0, 1, 1, 1, 2, 2, 3, 3]);
+
+    let weights = [1.0, 3.0];
+    let lens: Vec<_> = BalancedChunksMut::with_weights(&mut data, &weights)
+        .into_par_iter()
+        .map(|chunk| chunk.len())
+        .collect();
+    assert_eq!(lens, [3, 7]);
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_pi_stream_verification() {
+    use futures::StreamExt;
+
+    let n_digits = 2000;
+    let options = ParallelOptions { num_threads: 3, ..ParallelOptions::default() };
+    let stream = crate::async_pi::calculate_pi_parallel_stream(n_digits, options);
+    let actual = futures::executor::block_on(stream.collect::<Vec<u8>>());
+    verify_pi_digits(read_expected_digits(n_digits), actual.into_iter());
+
+    // Descartar o stream no meio do cálculo não deve travar
+    let stream = crate::async_pi::calculate_pi_tasks_stream(n_digits, 2, TaskOptions::default());
+    let first = futures::executor::block_on(stream.take(10).collect::<Vec<u8>>());
+    assert_eq!(first, [3, 1, 4, 1, 5, 9, 2, 6, 5, 3]);
+
+    // Cancelar o token do usuário encerra o stream, com o canal cheio e sem descartá-lo
+    let cancellation = CancellationToken::new();
+    let options = TaskOptions { cancellation: cancellation.clone(), ..TaskOptions::default() };
+    let mut stream = crate::async_pi::calculate_pi_tasks_stream(20_000, 2, options);
+    assert_eq!(futures::executor::block_on(stream.next()), Some(3));
+    cancellation.cancel();
+    assert!(futures::executor::block_on(stream.count()) < 20_000);
+}
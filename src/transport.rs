@@ -0,0 +1,158 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Tags das mensagens trocadas entre ranks.
+///
+/// Dados e controle em banda (`Carry`, `CarryBatch`, `Checkpoint` e `Terminate`) seguem o
+/// pipeline do rank 0 ao último rank e de volta, um rank por vez, e por isso chegam na
+/// ordem em que foram enviados. `Abort`, `Progress` e `Stats` vão direto ao destino.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tag {
+    /// Um único carry (lote de tamanho 1)
+    Carry = 0,
+    /// Lote de carries (um por dígito) trafegando pelo pipeline
+    CarryBatch = 1,
+    /// Fim dos dados: cada rank repassa ao anterior e termina
+    Terminate = 2,
+    /// Interrompe a computação sem processar os lotes pendentes. Enviada diretamente a
+    /// todos os outros ranks no cancelamento ou quando um rank falha, com o motivo (ver
+    /// `RankFailure::encode`).
+    Abort = 3,
+    /// Tempo ocioso acumulado de um worker (em ns), enviado periodicamente ao rank 0
+    Progress = 4,
+    /// Marcador em banda com o número de carries enviados antes dele. Cada rank confere
+    /// que processou exatamente esse número antes de repassá-lo; uma diferença indica
+    /// mensagens perdidas ou duplicadas e aborta a computação.
+    Checkpoint = 5,
+    /// Estatísticas finais de um worker, enviadas ao rank 0 quando ele termina
+    Stats = 6,
+}
+
+impl Tag {
+    pub fn as_i32(self) -> i32 {
+        self as i32
+    }
+
+    pub fn from_i32(tag: i32) -> Option<Self> {
+        [Self::Carry, Self::CarryBatch, Self::Terminate, Self::Abort, Self::Progress, Self::Checkpoint, Self::Stats]
+            .into_iter()
+            .find(|candidate| candidate.as_i32() == tag)
+    }
+
+    /// Tag de um lote de dados com `len` carries
+    pub(crate) fn for_batch(len: usize) -> Self {
+        match len {
+            1 => Self::Carry,
+            _ => Self::CarryBatch,
+        }
+    }
+}
+
+/// Mensagem recebida de outro rank
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    /// Rank de origem
+    pub source: i32,
+    /// Tag com que a mensagem foi enviada. Fica como `i32` para que uma tag desconhecida
+    /// chegue ao pipeline, que aborta a computação.
+    pub tag: i32,
+    pub data: Vec<i32>,
+}
+
+/// Comunicação ponto a ponto entre os ranks do pipeline distribuído.
+///
+/// A semântica é a do MPI: mensagens de uma mesma origem para um mesmo destino nunca se
+/// ultrapassam, e uma mensagem só é retirada quando casa com a origem e a tag pedidas.
+/// Nenhuma operação bloqueia indefinidamente, para que cada rank possa detectar vizinhos
+/// que pararam de responder.
+pub trait Transport {
+    /// Rank deste processo, de `0` a `size() - 1`
+    fn rank(&self) -> i32;
+
+    /// Número de ranks
+    fn size(&self) -> i32;
+
+    /// Envia `data` ao rank `destination`. Pode retornar antes da entrega (ver `flush`).
+    fn send(&self, destination: i32, tag: Tag, data: Vec<i32>);
+
+    /// Espera todos os envios pendentes terminarem. Retorna `false` se eles não terminarem
+    /// dentro de `timeout`, por exemplo porque o destino morreu.
+    fn flush(&self, timeout: Duration) -> bool;
+
+    /// Retira, sem bloquear, a primeira mensagem já recebida de `source` com `tag` (`None`
+    /// aceita qualquer origem ou tag)
+    fn try_receive(&self, source: Option<i32>, tag: Option<Tag>) -> Option<Message>;
+}
+
+/// Caixa de entrada de um rank, com as mensagens na ordem de chegada
+#[derive(Debug, Default)]
+pub(crate) struct Mailbox(Mutex<VecDeque<Message>>);
+
+impl Mailbox {
+    pub(crate) fn push(&self, message: Message) {
+        self.0.lock().unwrap().push_back(message);
+    }
+
+    /// Retira a primeira mensagem que casa com `source` e `tag` (ver `Transport::try_receive`)
+    pub(crate) fn take(&self, source: Option<i32>, tag: Option<Tag>) -> Option<Message> {
+        let mut messages = self.0.lock().unwrap();
+        let position = messages.iter().position(|message| {
+            source.is_none_or(|source| message.source == source) && tag.is_none_or(|tag| message.tag == tag.as_i32())
+        })?;
+        messages.remove(position)
+    }
+}
+
+/// Transporte em memória: cada rank é uma thread do mesmo processo e cada mensagem vai
+/// direto para a caixa de entrada do destino. Permite executar e testar o pipeline
+/// distribuído sem MPI.
+#[derive(Debug)]
+pub struct InMemoryTransport {
+    rank: i32,
+    mailboxes: Arc<Vec<Mailbox>>,
+}
+
+impl InMemoryTransport {
+    /// Cria os `size` ranks de uma simulação, ligados entre si. O índice é o rank; cada um
+    /// pode ser movido para a sua thread.
+    pub fn create(size: usize) -> Vec<Self> {
+        let mailboxes = Arc::new((0..size).map(|_| Mailbox::default()).collect::<Vec<_>>());
+        (0..size).map(|rank| Self { rank: rank as i32, mailboxes: mailboxes.clone() }).collect()
+    }
+}
+
+impl Transport for InMemoryTransport {
+    fn rank(&self) -> i32 {
+        self.rank
+    }
+
+    fn size(&self) -> i32 {
+        self.mailboxes.len() as i32
+    }
+
+    fn send(&self, destination: i32, tag: Tag, data: Vec<i32>) {
+        let message = Message { source: self.rank, tag: tag.as_i32(), data };
+        self.mailboxes[destination as usize].push(message);
+    }
+
+    /// A entrega é imediata, então nunca há envios pendentes
+    fn flush(&self, _timeout: Duration) -> bool {
+        true
+    }
+
+    fn try_receive(&self, source: Option<i32>, tag: Option<Tag>) -> Option<Message> {
+        self.mailboxes[self.rank as usize].take(source, tag)
+    }
+}
+
+/// Codifica valores `u64` como pares de `i32` (parte alta, parte baixa), para
+/// trafegarem nas mensagens do transporte
+pub(crate) fn encode_u64s(values: &[u64]) -> Vec<i32> {
+    values.iter().flat_map(|&value| [(value >> 32) as u32 as i32, value as u32 as i32]).collect()
+}
+
+/// Inverso de `encode_u64s`
+pub(crate) fn decode_u64s(data: &[i32]) -> Vec<u64> {
+    data.chunks_exact(2).map(|pair| ((pair[0] as u32 as u64) << 32) | pair[1] as u32 as u64).collect()
+}
@@ -0,0 +1,56 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Executável do backend com sockets, compilado pelo cargo para os testes de integração
+const SPIGOT_SOCKET: &str = env!("CARGO_BIN_EXE_spigot_socket");
+
+fn spawn(args: &[&str]) -> Child {
+    Command::new(SPIGOT_SOCKET)
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Não foi possível iniciar o spigot_socket")
+}
+
+#[test]
+fn test_pi_socket_processes() {
+    let n_digits = 500;
+    let workers = 2;
+
+    // O coordenador escuta numa porta escolhida pelo sistema e a anuncia no stderr
+    let mut coordinator = spawn(&["coordinator", "127.0.0.1:0", &workers.to_string(), &n_digits.to_string()]);
+    let mut stderr = BufReader::new(coordinator.stderr.take().unwrap());
+    let mut line = String::new();
+    stderr.read_line(&mut line).unwrap();
+    let address = line.trim_end().rsplit(' ').next().unwrap().to_string();
+
+    // Uma conexão que não segue o protocolo é descartada sem derrubar o coordenador, e uma
+    // que fica aberta sem enviar nada não o segura por todo o `receive_timeout` (60s)
+    let started = Instant::now();
+    let mut stray = TcpStream::connect(&address).unwrap();
+    stray.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+    drop(stray);
+    let silent = TcpStream::connect(&address).unwrap();
+
+    let handles = (0..workers).map(|_| spawn(&["worker", &address, "127.0.0.1:0"])).collect::<Vec<_>>();
+
+    let output = coordinator.wait_with_output().unwrap();
+    assert!(output.status.success(), "Coordenador terminou com {}", output.status);
+    assert!(started.elapsed() < Duration::from_secs(30), "Conexão silenciosa atrasou o coordenador");
+    drop(silent);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let actual = stdout.lines().nth(1).expect("Coordenador não imprimiu os dígitos");
+
+    let expected = fs::read_to_string("pi_dec_1m.txt").expect("Não foi possível abrir o arquivo pi_dec_1m.txt");
+    let expected = expected.chars().filter(char::is_ascii_digit).take(n_digits).collect::<String>();
+    assert_eq!(actual, expected);
+
+    for worker in handles {
+        let output = worker.wait_with_output().unwrap();
+        assert!(output.status.success(), "Worker terminou com {}: {}", output.status, String::from_utf8_lossy(&output.stderr));
+    }
+}